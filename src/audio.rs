@@ -0,0 +1,416 @@
+//! Sound cues played as the session moves along.
+//!
+//! Cues are worked out by comparing the program state before and after a
+//! change and handed to a `SoundSink`. With the `audio` feature sounds go to
+//! the default output device, otherwise they are dropped.
+
+use druid::{Data, Lens};
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::data::{AutoStepData, ProgramData};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cue {
+    PoseChange,
+    /// The pose is about to end.
+    SecondsLeft,
+    BlockChange,
+    SessionEnd,
+    /// A metronome tick.
+    Tick,
+    /// A metronome tick in the final seconds of a pose.
+    Accent,
+}
+
+#[derive(Clone, Debug, Data, Lens, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CueSettings {
+    pub enabled: bool,
+    /// From 0 to 1.
+    pub volume: f64,
+    /// WAV or OGG file played instead of the built-in sound.
+    pub file: Option<Arc<PathBuf>>,
+}
+
+impl Default for CueSettings {
+    fn default() -> Self {
+        CueSettings {
+            enabled: true,
+            volume: 0.5,
+            file: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Data, Lens, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundSettings {
+    pub pose_change: CueSettings,
+    pub seconds_left: CueSettings,
+    /// How long before the end of a pose `seconds_left` plays.
+    pub warning_seconds: usize,
+    pub block_change: CueSettings,
+    pub session_end: CueSettings,
+    /// Used for both metronome ticks and accents.
+    pub metronome: CueSettings,
+}
+
+impl Default for SoundSettings {
+    fn default() -> Self {
+        SoundSettings {
+            pose_change: CueSettings::default(),
+            seconds_left: CueSettings::default(),
+            warning_seconds: 5,
+            block_change: CueSettings::default(),
+            session_end: CueSettings::default(),
+            metronome: CueSettings::default(),
+        }
+    }
+}
+
+impl SoundSettings {
+    pub fn cue(&self, cue: Cue) -> &CueSettings {
+        match cue {
+            Cue::PoseChange => &self.pose_change,
+            Cue::SecondsLeft => &self.seconds_left,
+            Cue::BlockChange => &self.block_change,
+            Cue::SessionEnd => &self.session_end,
+            Cue::Tick | Cue::Accent => &self.metronome,
+        }
+    }
+}
+
+/// Somewhere to play cues.
+pub trait SoundSink {
+    fn play(&mut self, cue: Cue, settings: &CueSettings);
+}
+
+/// Drops every cue.
+pub struct NullSink;
+
+impl SoundSink for NullSink {
+    fn play(&mut self, _cue: Cue, _settings: &CueSettings) {}
+}
+
+/// Remembers the cues played instead of making any sound.
+#[cfg(test)]
+#[derive(Default)]
+pub struct RecordingSink {
+    pub played: Vec<(Cue, f64)>,
+}
+
+#[cfg(test)]
+impl SoundSink for RecordingSink {
+    fn play(&mut self, cue: Cue, settings: &CueSettings) {
+        self.played.push((cue, settings.volume));
+    }
+}
+
+#[cfg(feature = "audio")]
+pub use rodio_sink::RodioSink;
+
+#[cfg(feature = "audio")]
+mod rodio_sink {
+    use rodio::source::Source;
+    use rodio::{Decoder, OutputStream, OutputStreamHandle};
+
+    use std::fs::File;
+    use std::io::{BufReader, Cursor};
+
+    use super::{Cue, CueSettings, SoundSink};
+
+    /// Built-in sound of a cue, bundled from `assets/sounds`.
+    fn built_in(cue: Cue) -> &'static [u8] {
+        match cue {
+            Cue::PoseChange => include_bytes!("../assets/sounds/pose_change.wav"),
+            Cue::SecondsLeft => include_bytes!("../assets/sounds/seconds_left.wav"),
+            Cue::BlockChange => include_bytes!("../assets/sounds/block_change.wav"),
+            Cue::SessionEnd => include_bytes!("../assets/sounds/session_end.wav"),
+            Cue::Tick => include_bytes!("../assets/sounds/tick.wav"),
+            Cue::Accent => include_bytes!("../assets/sounds/accent.wav"),
+        }
+    }
+
+    /// Plays cues on the default output device.
+    pub struct RodioSink {
+        // Sounds stop playing once the stream is dropped.
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+    }
+
+    impl RodioSink {
+        pub fn new() -> Option<Self> {
+            let (stream, handle) = OutputStream::try_default().ok()?;
+            Some(RodioSink {
+                _stream: stream,
+                handle,
+            })
+        }
+    }
+
+    impl SoundSink for RodioSink {
+        fn play(&mut self, cue: Cue, settings: &CueSettings) {
+            let volume = settings.volume as f32;
+            let file = settings
+                .file
+                .as_ref()
+                .and_then(|path| File::open(path.as_ref()).ok())
+                .and_then(|file| Decoder::new(BufReader::new(file)).ok());
+
+            let played = match file {
+                Some(source) => self
+                    .handle
+                    .play_raw(source.amplify(volume).convert_samples()),
+                // A file that can't be read falls back to the built-in sound.
+                None => match Decoder::new(Cursor::new(built_in(cue))) {
+                    Ok(source) => self
+                        .handle
+                        .play_raw(source.amplify(volume).convert_samples()),
+                    Err(_) => return,
+                },
+            };
+            played.ok();
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn built_in_sounds_decode() {
+            for &cue in [
+                Cue::PoseChange,
+                Cue::SecondsLeft,
+                Cue::BlockChange,
+                Cue::SessionEnd,
+                Cue::Tick,
+                Cue::Accent,
+            ]
+            .iter()
+            {
+                let source = Decoder::new(Cursor::new(built_in(cue))).unwrap();
+                assert!(source.total_duration().is_some(), "{:?}", cue);
+            }
+        }
+    }
+}
+
+/// The sink sounds go to when the app runs.
+pub fn default_sink() -> Box<dyn SoundSink> {
+    #[cfg(feature = "audio")]
+    if let Some(sink) = RodioSink::new() {
+        return Box::new(sink);
+    }
+    Box::new(NullSink)
+}
+
+/// Works out the cues for a change of the program state from `old` to `new`.
+pub fn cues(old: &ProgramData, new: &ProgramData) -> Vec<Cue> {
+    let mut cues = vec![];
+
+    // A looping or repeating session carries on, so the end of the schedule
+    // is counted rather than read off the state.
+    let ended = new.schedule_ends != old.schedule_ends;
+    if ended {
+        cues.push(Cue::SessionEnd);
+    }
+
+    if let (Some(old_step), Some(new_step)) = (old.state.get_data(), new.state.get_data()) {
+        let block_changed =
+            new_step.on_break != old_step.on_break || new_step.current.0 != old_step.current.0;
+        // Going back to an earlier block at the end plays the session end
+        // cue only.
+        if block_changed && !ended {
+            cues.push(Cue::BlockChange);
+        } else if new_step.current != old_step.current && !ended {
+            cues.push(Cue::PoseChange);
+        }
+
+        let warning = new.config.sounds.warning_seconds as f64;
+        let crossed = match (old_step.time_left, new_step.time_left) {
+            (Some(old_left), Some(new_left)) => old_left > warning && new_left <= warning,
+            _ => false,
+        };
+        if crossed && warning > 0. && !new_step.on_break {
+            cues.push(Cue::SecondsLeft);
+        }
+    }
+
+    cues.retain(|&cue| new.config.sounds.cue(cue).enabled);
+    cues
+}
+
+/// Plays the cues for a change of the program state from `old` to `new`.
+pub fn play_cues(sink: &mut dyn SoundSink, old: &ProgramData, new: &ProgramData) {
+    for cue in cues(old, new) {
+        sink.play(cue, new.config.sounds.cue(cue));
+    }
+}
+
+/// Works out the metronome cue for the session timer counting the current
+/// pose down from `old_left` seconds. Breaks and lead-ins stay silent.
+pub fn metronome(step: &AutoStepData, old_left: f64) -> Option<Cue> {
+    let new_left = step.time_left?;
+    if step.on_break || step.lead_in.is_some() || new_left <= 0. {
+        return None;
+    }
+    let options = &step.schedule.block(step.current.0).options;
+
+    if new_left <= options.accent_seconds as f64 {
+        return (old_left.ceil() > new_left.ceil()).then_some(Cue::Accent);
+    }
+    if options.metronome == 0 {
+        return None;
+    }
+
+    let interval = options.metronome as f64;
+    let duration = step.get_current_duration() as f64;
+    let ticks = |left: f64| ((duration - left) / interval).floor();
+    (ticks(new_left) > ticks(old_left)).then_some(Cue::Tick)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::tests::{data_with_image, start};
+    use crate::data::{EndPolicy, Step};
+    use crate::schedule::{Schedule, ScheduleBlock};
+    use std::time::Duration;
+
+    /// A session of `blocks` that has just started.
+    fn session(test: &str, policy: EndPolicy, blocks: Vec<ScheduleBlock>) -> ProgramData {
+        let mut data = data_with_image(test);
+        data.config.end_policy = policy;
+        data.config.schedule = Schedule::new(blocks);
+        start(&mut data);
+        data
+    }
+
+    fn advance(data: &mut ProgramData, step: Step) -> Vec<Cue> {
+        let old = data.clone();
+        data.advance(step);
+        let mut sink = RecordingSink::default();
+        play_cues(&mut sink, &old, data);
+        sink.played.into_iter().map(|(cue, _)| cue).collect()
+    }
+
+    #[test]
+    fn poses_and_blocks_change() {
+        let mut first = ScheduleBlock::new(2, 30);
+        first.break_after = Some(10);
+        let mut data = session(
+            "poses_and_blocks_change",
+            EndPolicy::Stop,
+            vec![first, ScheduleBlock::new(1, 30)],
+        );
+
+        assert_eq!(advance(&mut data, Step::Pose), vec![Cue::PoseChange]);
+        // Into the break, then out of it into the next block.
+        assert_eq!(advance(&mut data, Step::Pose), vec![Cue::BlockChange]);
+        assert_eq!(advance(&mut data, Step::Pose), vec![Cue::BlockChange]);
+    }
+
+    #[test]
+    fn session_end_plays_under_every_end_policy() {
+        for &policy in [
+            EndPolicy::Stop,
+            EndPolicy::Loop,
+            EndPolicy::RepeatLastBlock,
+            EndPolicy::Pause,
+        ]
+        .iter()
+        {
+            let mut data = session("session_end_plays", policy, vec![ScheduleBlock::new(1, 30)]);
+
+            assert_eq!(
+                advance(&mut data, Step::Pose),
+                vec![Cue::SessionEnd],
+                "{:?}",
+                policy
+            );
+            // The session carries on and ends again.
+            if policy != EndPolicy::Stop {
+                assert_eq!(
+                    advance(&mut data, Step::Pose),
+                    vec![Cue::SessionEnd],
+                    "{:?} again",
+                    policy
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stopping_early_is_not_a_session_end() {
+        let mut data = session(
+            "stopping_early",
+            EndPolicy::Stop,
+            vec![ScheduleBlock::new(2, 30)],
+        );
+        let old = data.clone();
+        data.stop();
+        assert_eq!(cues(&old, &data), vec![]);
+    }
+
+    #[test]
+    fn disabled_cues_stay_silent() {
+        let mut data = session(
+            "disabled_cues",
+            EndPolicy::Loop,
+            vec![ScheduleBlock::new(1, 30)],
+        );
+        data.config.sounds.session_end.enabled = false;
+        data.config.sounds.pose_change.volume = 0.25;
+
+        assert_eq!(advance(&mut data, Step::Pose), vec![]);
+        data.config.schedule = Schedule::new(vec![ScheduleBlock::new(2, 30)]);
+        start(&mut data);
+        let old = data.clone();
+        data.advance(Step::Pose);
+        let mut sink = RecordingSink::default();
+        play_cues(&mut sink, &old, &data);
+        assert_eq!(sink.played, vec![(Cue::PoseChange, 0.25)]);
+    }
+
+    #[test]
+    fn seconds_left_plays_once() {
+        let mut data = session(
+            "seconds_left",
+            EndPolicy::Stop,
+            vec![ScheduleBlock::new(1, 30)],
+        );
+        let mut played = vec![];
+        for _ in 0..29 {
+            let old = data.clone();
+            if let Some(step) = data.state.get_data_mut() {
+                step.tick(Duration::from_secs(1));
+            }
+            played.extend(cues(&old, &data));
+        }
+        assert_eq!(played, vec![Cue::SecondsLeft]);
+    }
+
+    #[test]
+    fn metronome_ticks_and_accents_the_final_seconds() {
+        let mut block = ScheduleBlock::new(1, 30);
+        block.options.metronome = 10;
+        block.options.accent_seconds = 3;
+        let mut data = session("metronome", EndPolicy::Stop, vec![block]);
+
+        let mut played = vec![];
+        let step = data.state.get_data_mut().unwrap();
+        while step.time_left.is_some_and(|left| left > 0.) {
+            let old_left = step.time_left.unwrap();
+            step.tick(Duration::from_millis(250));
+            played.extend(metronome(step, old_left));
+        }
+        assert_eq!(
+            played,
+            vec![Cue::Tick, Cue::Tick, Cue::Accent, Cue::Accent, Cue::Accent]
+        );
+    }
+}
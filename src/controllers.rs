@@ -1,14 +1,16 @@
 use druid::{
-    widget::{Controller, Image},
+    widget::{Controller, Image, TextBoxEvent, ValidationDelegate},
     Data, EventCtx,
 };
-use druid::{Env, Event, ImageBuf, TimerToken, UpdateCtx, Widget};
+use druid::{Env, Event, ImageBuf, LifeCycle, LifeCycleCtx, TimerToken, UpdateCtx, Widget};
 
 use std::sync::Arc;
 use std::time::Duration;
 use std::time::Instant;
 
+use crate::audio::{default_sink, metronome, play_cues, SoundSink};
 use crate::data::*;
+use crate::keys::{find, Action};
 
 pub struct UpdateImage;
 
@@ -37,9 +39,69 @@ impl Controller<Option<Arc<ImageBuf>>, Image> for UpdateImage {
     }
 }
 
+/// Reports parse errors from the schedule notation text box.
+pub struct NotationErrors;
+
+impl ValidationDelegate for NotationErrors {
+    fn event(&mut self, ctx: &mut EventCtx, event: TextBoxEvent, _current_text: &str) {
+        match event {
+            TextBoxEvent::Invalid(error) => {
+                ctx.submit_command(SET_NOTATION_ERROR.with(Some(error.to_string())))
+            }
+            TextBoxEvent::Complete | TextBoxEvent::Cancel => {
+                ctx.submit_command(SET_NOTATION_ERROR.with(None))
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Updates the wrapped widget every `interval` even if its data doesn't change,
+/// for text that depends on the clock.
+pub struct Refresh {
+    interval: Duration,
+    timer_id: TimerToken,
+}
+
+impl Refresh {
+    pub fn every(interval: Duration) -> Self {
+        Refresh {
+            interval,
+            timer_id: TimerToken::INVALID,
+        }
+    }
+}
+
+impl<T, W: Widget<T>> Controller<T, W> for Refresh {
+    fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        match event {
+            Event::Timer(id) if id == &self.timer_id => {
+                ctx.request_update();
+                self.timer_id = ctx.request_timer(self.interval);
+            }
+            _ => child.event(ctx, event, data, env),
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        child: &mut W,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &T,
+        env: &Env,
+    ) {
+        if let LifeCycle::WidgetAdded = event {
+            self.timer_id = ctx.request_timer(self.interval);
+        }
+        child.lifecycle(ctx, event, data, env)
+    }
+}
+
 pub struct AutoStepControl {
     pub timer_id: TimerToken,
     pub start_time: Option<Instant>,
+    pub sound: Box<dyn SoundSink>,
 }
 
 impl AutoStepControl {
@@ -47,6 +109,7 @@ impl AutoStepControl {
         AutoStepControl {
             timer_id: TimerToken::INVALID,
             start_time: None,
+            sound: default_sink(),
         }
     }
 }
@@ -63,6 +126,13 @@ impl<W: Widget<ProgramData>> Controller<ProgramData, W> for AutoStepControl {
         if !old_data.config.same(&data.config) {
             data.config.try_save().ok();
         }
+        if !old_data.presets.same(&data.presets) {
+            data.presets.try_save().ok();
+        }
+        if !old_data.progress.same(&data.progress) {
+            data.progress.try_save().ok();
+        }
+        play_cues(self.sound.as_mut(), old_data, data);
         child.update(ctx, old_data, data, env);
     }
 
@@ -77,36 +147,34 @@ impl<W: Widget<ProgramData>> Controller<ProgramData, W> for AutoStepControl {
         match event {
             Event::Timer(id) if id == &self.timer_id => {
                 let now = Instant::now();
-                let mut end = false;
-                match data.state {
-                    AutoStepState::Paused(ref mut auto_step_data)
-                    | AutoStepState::Playing(ref mut auto_step_data) => {
-                        if let Some(time_left) = auto_step_data.time_left {
-                            auto_step_data.time_left = Duration::from_secs_f64(time_left)
-                                .checked_sub(now - self.start_time.unwrap())
-                                .map(|d| d.as_secs_f64());
-                        } else {
-                            end = auto_step_data.set_next_image(data.images_paths.as_slice());
-                            auto_step_data.step_forward(data.config.schedule.as_slice());
-                            auto_step_data.time_left = Some(
-                                auto_step_data.get_current_duration(data.config.schedule.as_slice())
-                                    as f64,
-                            );
-                            data.reset_transformations();
-                        }
+                let mut pose_ended = false;
+                // A paused or stopped session lets the timer lapse.
+                if let AutoStepState::Playing(ref mut auto_step_data) = data.state {
+                    if let Some(old_left) = auto_step_data.time_left {
+                        let counting = auto_step_data.lead_in.is_none();
+                        auto_step_data.tick(now - self.start_time.unwrap());
 
-                        self.start_time = Some(now);
-                        self.timer_id = ctx.request_timer(Duration::from_millis(20));
+                        let cue = metronome(auto_step_data, old_left).filter(|_| counting);
+                        if let Some(cue) = cue {
+                            let settings = data.config.sounds.cue(cue);
+                            if settings.enabled {
+                                self.sound.play(cue, settings);
+                            }
+                        }
+                    } else {
+                        pose_ended = true;
                     }
-                    AutoStepState::Stopped => (),
+
+                    self.start_time = Some(now);
+                    self.timer_id = ctx.request_timer(Duration::from_millis(20));
                 }
-                if end {
-                    data.prepare_images(false);
-                    data.reset_transformations();
+                if pose_ended {
+                    data.advance(Step::Pose);
                 }
             }
             Event::Command(cmd) if cmd.is(START_AUTO_STEP) => {
                 let now = Instant::now();
+                let mut started = false;
 
                 data.state = match data.state.clone() {
                     AutoStepState::Paused(auto_step_data) => {
@@ -114,31 +182,37 @@ impl<W: Widget<ProgramData>> Controller<ProgramData, W> for AutoStepControl {
                         self.start_time = Some(now);
                         AutoStepState::Playing(auto_step_data)
                     }
-                    AutoStepState::Stopped => {
-                        self.timer_id = ctx.request_timer(Duration::from_millis(20));
-                        self.start_time = Some(now);
-                        data.reset_transformations();
-                        AutoStepState::Playing(AutoStepData::new(data))
-                    }
-                    AutoStepState::Playing(ref mut auto_step_data) => {
-                        if let Some(time_left) = auto_step_data.time_left {
-                            auto_step_data.time_left = Duration::from_secs_f64(time_left)
-                                .checked_sub(now - self.start_time.unwrap())
-                                .map(|d| d.as_secs_f64());
+                    AutoStepState::Stopped => match AutoStepData::new(data) {
+                        Ok(auto_step_data) => {
+                            self.timer_id = ctx.request_timer(Duration::from_millis(20));
+                            self.start_time = Some(now);
+                            started = true;
+                            data.error = None;
+                            data.summary = None;
+                            AutoStepState::Playing(auto_step_data)
+                        }
+                        Err(error) => {
+                            data.error = Some(error.to_string());
+                            AutoStepState::Stopped
                         }
+                    },
+                    AutoStepState::Playing(ref mut auto_step_data) => {
+                        auto_step_data.tick(now - self.start_time.unwrap());
 
                         self.timer_id = TimerToken::INVALID;
                         self.start_time = None;
                         AutoStepState::Paused(auto_step_data.clone())
                     }
+                };
+
+                if started {
+                    data.reset_transformations();
                 }
             }
             Event::Command(cmd) if cmd.is(STOP_AUTO_STEP) => {
-                data.state = AutoStepState::Stopped;
                 self.start_time = None;
                 self.timer_id = TimerToken::INVALID;
-                data.prepare_images(false);
-                data.reset_transformations();
+                data.stop();
             }
             Event::Command(cmd) if cmd.is(TOGGLE_BW) => {
                 data.black_and_white = !data.black_and_white;
@@ -166,3 +240,49 @@ impl<W: Widget<ProgramData>> Controller<ProgramData, W> for AutoStepControl {
         child.event(ctx, event, data, env)
     }
 }
+
+/// Runs the Player actions bound to keys in the config. The Player tab takes
+/// keyboard focus when the window opens and when it is clicked, so keys typed
+/// into text boxes elsewhere don't trigger shortcuts.
+pub struct KeyboardControl;
+
+impl<W: Widget<ProgramData>> Controller<ProgramData, W> for KeyboardControl {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut ProgramData,
+        env: &Env,
+    ) {
+        match event {
+            Event::WindowConnected | Event::MouseDown(_) => ctx.request_focus(),
+            Event::KeyDown(key) if !key.repeat => {
+                if let Some(action) = find(&data.config.key_bindings, key) {
+                    perform(ctx, data, action);
+                    ctx.set_handled();
+                }
+            }
+            _ => (),
+        }
+
+        child.event(ctx, event, data, env)
+    }
+}
+
+fn perform(ctx: &mut EventCtx, data: &mut ProgramData, action: Action) {
+    match action {
+        Action::PlayPause => ctx.submit_command(START_AUTO_STEP),
+        Action::Reload => data.reload(),
+        Action::Pin => data.toggle_pin(),
+        Action::Back => data.back(),
+        Action::Skip => data.advance(Step::Pose),
+        Action::SkipBlock => data.advance(Step::Block),
+        Action::AddHalfMinute => data.adjust_time(30.),
+        Action::AddMinute => data.adjust_time(60.),
+        Action::RemoveHalfMinute => data.adjust_time(-30.),
+        Action::Stop => ctx.submit_command(STOP_AUTO_STEP),
+        Action::BlackAndWhite => ctx.submit_command(TOGGLE_BW),
+        Action::Mirror => ctx.submit_command(TOGGLE_MIRROR),
+    }
+}
@@ -0,0 +1,117 @@
+//! Multi-day practice programs, read from `curriculum.ron` in the config
+//! directory:
+//!
+//! ```ron
+//! (
+//!     name: "Figure drawing",
+//!     days: [
+//!         (name: "Day 1", schedule: (blocks: [(count: 20, duration: 30)])),
+//!         (
+//!             name: "Day 2",
+//!             schedule: (blocks: [(count: 10, duration: 60)]),
+//!             filter: Some("hands"),
+//!             notes: "Only hands today",
+//!         ),
+//!     ],
+//! )
+//! ```
+//!
+//! Completed days are remembered in `progress.ron`.
+
+use druid::{Data, Lens};
+
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::sync::Arc;
+
+use crate::data::{get_curriculum_path, get_progress_path, load_ron, save_ron};
+use crate::schedule::{Schedule, ScheduleBlock};
+
+#[derive(Clone, Data, Lens, Serialize, Deserialize)]
+pub struct CurriculumDay {
+    pub name: String,
+    pub schedule: Schedule,
+    /// Image pool for the blocks of the day that don't pick their own.
+    #[serde(default)]
+    pub filter: Option<String>,
+    #[serde(default)]
+    pub notes: String,
+}
+
+impl CurriculumDay {
+    /// The day's schedule with the filter applied.
+    pub fn practice_schedule(&self) -> Schedule {
+        let blocks = self
+            .schedule
+            .blocks
+            .iter()
+            .map(|block| {
+                let mut block = block.clone();
+                if block.options.pool.is_none() {
+                    block.options.pool = self.filter.clone();
+                }
+                block
+            })
+            .collect::<Vec<ScheduleBlock>>();
+        Schedule::new(blocks)
+    }
+}
+
+#[derive(Clone, Data, Lens, Serialize, Deserialize)]
+pub struct Curriculum {
+    pub name: String,
+    pub days: Arc<Vec<CurriculumDay>>,
+}
+
+impl Curriculum {
+    pub fn load() -> Option<Self> {
+        load_ron(get_curriculum_path())
+    }
+}
+
+#[derive(Clone, Default, Data, Lens, Serialize, Deserialize)]
+pub struct Progress {
+    /// Name of the curriculum the progress belongs to.
+    pub curriculum: String,
+    /// Indices of the completed days.
+    pub completed: Arc<Vec<usize>>,
+}
+
+impl Progress {
+    /// Loads the progress through `curriculum`, starting over if the saved
+    /// progress belongs to another one.
+    pub fn new(curriculum: Option<&Curriculum>) -> Self {
+        let name = curriculum.map_or(String::new(), |c| c.name.clone());
+
+        load_ron(get_progress_path())
+            .filter(|progress: &Progress| progress.curriculum == name)
+            .unwrap_or(Progress {
+                curriculum: name,
+                completed: Arc::new(vec![]),
+            })
+    }
+
+    pub fn try_save(&self) -> io::Result<()> {
+        save_ron(get_progress_path(), self)
+    }
+
+    pub fn complete(&mut self, day: usize) {
+        if !self.completed.contains(&day) {
+            Arc::make_mut(&mut self.completed).push(day);
+        }
+    }
+
+    /// The first day that hasn't been completed yet.
+    pub fn today(&self, curriculum: &Curriculum) -> Option<usize> {
+        (0..curriculum.days.len()).find(|day| !self.completed.contains(day))
+    }
+}
+
+/// A curriculum day loaded as the current schedule.
+#[derive(Clone, Data)]
+pub struct Practice {
+    pub day: usize,
+    /// The schedule loaded for the day, a session only counts towards the
+    /// day while the schedule is left unchanged.
+    pub schedule: Schedule,
+}
@@ -7,71 +7,252 @@ use druid::Selector;
 use directories::ProjectDirs;
 use ron::de::from_reader;
 use ron::ser::{to_writer_pretty, PrettyConfig};
-use serde::{Deserialize, Serialize};
-use std::fs;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::{create_dir_all, File};
 use std::io;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use rand::rngs::ThreadRng;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 
-pub fn get_cache_path() -> Option<PathBuf> {
+use crate::audio::SoundSettings;
+use crate::curriculum::{Curriculum, Practice, Progress};
+use crate::images::{
+    in_pool, load_meta, requeue, scan_images, DurationPolicy, ImageMeta, ImagePool,
+};
+use crate::keys::{default_bindings, KeyBinding};
+use crate::planner::{PlannerSettings, RampSettings};
+use crate::presets::Presets;
+use crate::schedule::{Schedule, ScheduleBlock, ScheduleError, ValidSchedule};
+
+fn get_config_file_path(file_name: &str) -> Option<PathBuf> {
     ProjectDirs::from("com", "Real Complexity", "Art Practice").map(|proj_dirs| {
         proj_dirs
             .config_dir()
-            .join(Path::new("config.ron"))
+            .join(Path::new(file_name))
             .to_path_buf()
     })
 }
 
+pub fn get_cache_path() -> Option<PathBuf> {
+    get_config_file_path("config.ron")
+}
+
+pub fn get_presets_path() -> Option<PathBuf> {
+    get_config_file_path("presets.ron")
+}
+
+pub fn get_curriculum_path() -> Option<PathBuf> {
+    get_config_file_path("curriculum.ron")
+}
+
+pub fn get_progress_path() -> Option<PathBuf> {
+    get_config_file_path("progress.ron")
+}
+
+pub fn load_ron<T: DeserializeOwned>(path: Option<PathBuf>) -> Option<T> {
+    path.map(|path| File::open(path).ok().map(|f| from_reader(f).ok()).flatten())
+        .flatten()
+}
+
+pub fn save_ron<T: Serialize>(path: Option<PathBuf>, value: &T) -> io::Result<()> {
+    let pretty = PrettyConfig::new()
+        .depth_limit(3)
+        .separate_tuple_members(true)
+        .enumerate_arrays(true);
+
+    path.map(|path| {
+        create_dir_all(
+            path.parent()
+                .ok_or(io::Error::new(io::ErrorKind::Other, "unable to create dir"))?,
+        )?;
+        File::create(path).and_then(|f| {
+            to_writer_pretty(f, value, pretty)
+                .map_err(|_| io::Error::new(io::ErrorKind::Other, "can't save"))
+        })
+    })
+    .ok_or(io::Error::new(io::ErrorKind::Other, "oh no!"))?
+}
+
 pub const START_AUTO_STEP: Selector<()> = Selector::new("start_auto_step");
 pub const STOP_AUTO_STEP: Selector<()> = Selector::new("stop_auto_step");
 
 pub const TOGGLE_BW: Selector<()> = Selector::new("toggle_bw");
 pub const TOGGLE_MIRROR: Selector<()> = Selector::new("toggle_mirror");
 
+pub const SET_NOTATION_ERROR: Selector<Option<String>> = Selector::new("set_notation_error");
+
+pub const LOAD_PRESET: Selector<usize> = Selector::new("load_preset");
+pub const SAVE_PRESET: Selector<()> = Selector::new("save_preset");
+pub const DUPLICATE_PRESET: Selector<usize> = Selector::new("duplicate_preset");
+pub const DELETE_PRESET: Selector<usize> = Selector::new("delete_preset");
+
+pub const JUMP_TO_BLOCK: Selector<usize> = Selector::new("jump_to_block");
+pub const REQUEUE_UPCOMING: Selector<usize> = Selector::new("requeue_upcoming");
+/// A thumbnail decoded in the background, `None` if the image can't be read.
+pub const THUMBNAIL_READY: Selector<(PathBuf, Option<ImageBuf>)> = Selector::new("thumbnail_ready");
+
+pub const COPY_SHARE_CODE: Selector<()> = Selector::new("copy_share_code");
+pub const IMPORT_SHARE_CODE: Selector<()> = Selector::new("import_share_code");
+
+/// What the player does once the last block of the schedule is over.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Data, Serialize, Deserialize)]
+pub enum EndPolicy {
+    /// Stop the session and show a summary.
+    #[default]
+    Stop,
+    /// Start over from the first block.
+    Loop,
+    /// Keep playing the last block.
+    RepeatLastBlock,
+    /// Go back to the first block, but wait for Play before continuing.
+    Pause,
+}
+
+/// Countdowns shown before poses start, in seconds, 0 meaning none.
+#[derive(Clone, Debug, Default, Data, Lens, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LeadIn {
+    pub every_pose: usize,
+    /// Used for the first pose of a session if it is longer.
+    pub session_start: usize,
+    /// Hide the upcoming image during the countdown instead of dimming it.
+    pub hide_image: bool,
+}
+
+/// How the time left of a pose is shown on the Player tab.
+#[derive(Clone, Debug, Data, Lens, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimerDisplay {
+    pub progress_bar: bool,
+    /// Seconds before the end of a pose the timer turns to the warning
+    /// colour, 0 for never. The warning sound has its own setting in
+    /// `SoundSettings`.
+    pub warning_seconds: usize,
+    /// Blink the timer during the warning instead of just colouring it.
+    pub flash: bool,
+    /// Also show the time left in large type over the image.
+    pub overlay: bool,
+}
+
+impl Default for TimerDisplay {
+    fn default() -> Self {
+        TimerDisplay {
+            progress_bar: true,
+            warning_seconds: 10,
+            flash: false,
+            overlay: false,
+        }
+    }
+}
+
+fn default_break_message() -> String {
+    "Take a break".to_owned()
+}
+
+/// A schedule that is only used for this run, like one given on the command
+/// line or a curriculum day.
+#[derive(Clone, Data)]
+pub struct ScheduleOverride {
+    schedule: Schedule,
+    /// The schedule from the config file, saved in place of `schedule`.
+    saved: Schedule,
+}
+
 #[derive(Clone, Data, Lens, Serialize, Deserialize)]
 pub struct Config {
     pub current_directory: Arc<Option<PathBuf>>,
-    pub schedule: Arc<Vec<(usize, usize)>>,
+    pub schedule: Schedule,
+    /// Shown on the rest screen during breaks, may be left empty.
+    #[serde(default = "default_break_message")]
+    pub break_message: String,
+    #[serde(default)]
+    pub end_policy: EndPolicy,
+    #[serde(default)]
+    pub planner: PlannerSettings,
+    #[serde(default)]
+    pub ramp: RampSettings,
+    /// Look for images in the subfolders of the images directory too, which
+    /// folder pools need.
+    #[serde(default)]
+    pub scan_subfolders: bool,
+    #[serde(default)]
+    pub duration_policy: DurationPolicy,
+    #[serde(default)]
+    pub lead_in: LeadIn,
+    #[serde(default)]
+    pub sounds: SoundSettings,
+    #[serde(default)]
+    pub timer: TimerDisplay,
+    /// Number of upcoming images previewed on the Player tab.
+    #[serde(default)]
+    pub preview: usize,
+    #[serde(default = "default_bindings")]
+    pub key_bindings: Arc<Vec<KeyBinding>>,
+    #[serde(skip)]
+    schedule_override: Option<ScheduleOverride>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            current_directory: Arc::new(None),
+            schedule: Schedule::default(),
+            break_message: default_break_message(),
+            end_policy: EndPolicy::default(),
+            planner: PlannerSettings::default(),
+            ramp: RampSettings::default(),
+            scan_subfolders: false,
+            duration_policy: DurationPolicy::default(),
+            lead_in: LeadIn::default(),
+            sounds: SoundSettings::default(),
+            timer: TimerDisplay::default(),
+            preview: 0,
+            key_bindings: default_bindings(),
+            schedule_override: None,
+        }
+    }
 }
 
 impl Config {
     pub fn new() -> Self {
-        let cached_config: Option<Config> = get_cache_path()
-            .map(|path| File::open(path).ok().map(|f| from_reader(f).ok()).flatten())
-            .flatten();
+        load_ron(get_cache_path()).unwrap_or_default()
+    }
 
-        if let Some(config) = cached_config {
-            config
-        } else {
-            Config {
-                current_directory: Arc::new(None),
-                schedule: Arc::new(vec![(5, 30), (5, 60)]),
+    /// Uses `schedule` for this run without saving it, unless it is edited.
+    pub fn override_schedule(&mut self, schedule: Schedule) {
+        let saved = match self.schedule_override.take() {
+            // Replacing one override with another keeps the saved schedule.
+            Some(old) if old.schedule.same(&self.schedule) => old.saved,
+            _ => self.schedule.clone(),
+        };
+        self.schedule = schedule.clone();
+        self.schedule_override = Some(ScheduleOverride { schedule, saved });
+    }
+
+    /// The schedule written to the config file.
+    fn saved_schedule(&self) -> &Schedule {
+        match &self.schedule_override {
+            Some(schedule_override) if schedule_override.schedule.same(&self.schedule) => {
+                &schedule_override.saved
             }
+            _ => &self.schedule,
         }
     }
+
     pub fn try_save(&self) -> io::Result<()> {
-        let pretty = PrettyConfig::new()
-            .depth_limit(2)
-            .separate_tuple_members(true)
-            .enumerate_arrays(true);
-
-        get_cache_path()
-            .map(|path| {
-                create_dir_all(
-                    path.parent()
-                        .ok_or(io::Error::new(io::ErrorKind::Other, "unable to create dir"))?,
-                )?;
-                File::create(path).and_then(|f| {
-                    to_writer_pretty(f, self, pretty)
-                        .map_err(|_| io::Error::new(io::ErrorKind::Other, "can't save"))
-                })
-            })
-            .ok_or(io::Error::new(io::ErrorKind::Other, "oh no!"))?
+        if self.saved_schedule().same(&self.schedule) {
+            save_ron(get_cache_path(), self)
+        } else {
+            let mut config = self.clone();
+            config.schedule = self.saved_schedule().clone();
+            save_ron(get_cache_path(), &config)
+        }
     }
 }
 
@@ -79,48 +260,326 @@ impl Config {
 /// The main model for a todo list application.
 pub struct ProgramData {
     pub images_paths: Arc<Vec<PathBuf>>,
+    /// Sidecar metadata of the images in the current directory.
+    pub image_meta: Arc<HashMap<PathBuf, ImageMeta>>,
     pub config: Config,
+    pub presets: Presets,
+    pub curriculum: Option<Curriculum>,
+    pub progress: Progress,
+    /// The curriculum day loaded with "Today's practice", if any.
+    pub practice: Option<Practice>,
     pub state: AutoStepState,
     pub rng: Arc<RwLock<ThreadRng>>,
     pub black_and_white: bool,
     pub mirrored: bool,
+    pub error: Option<String>,
+    pub notation_error: Option<String>,
+    /// Outcome of the last schedule import or export.
+    pub share_status: Option<String>,
+    /// Statistics of the last session that ran to the end of its schedule.
+    pub summary: Option<SessionStats>,
+    /// How many times a session has run past the end of its schedule, loops
+    /// and repeats included.
+    pub schedule_ends: usize,
 }
 
 impl ProgramData {
     pub fn new() -> Self {
+        let curriculum = Curriculum::load();
         let mut data = ProgramData {
             images_paths: Arc::new(vec![]),
+            image_meta: Arc::new(HashMap::new()),
             config: Config::new(),
+            presets: Presets::new(),
+            progress: Progress::new(curriculum.as_ref()),
+            curriculum,
+            practice: None,
             state: AutoStepState::Stopped,
             rng: Arc::new(RwLock::new(thread_rng())),
             black_and_white: false,
             mirrored: false,
+            error: None,
+            notation_error: None,
+            share_status: None,
+            summary: None,
+            schedule_ends: 0,
         };
         data.prepare_images(true);
         data
     }
 
+    /// Moves the running session on to the next pose or block, picking a new
+    /// image unless the session is entering a break. What happens past the
+    /// last block is decided by the configured `EndPolicy`.
+    ///
+    /// Stepping by a pose after going back shows the poses of the history
+    /// again instead of new images, with their breaks and lead-ins.
+    pub fn advance(&mut self, step: Step) {
+        let mut replayed = None;
+        let mut end = false;
+        let mut finished = false;
+        let mut pause = false;
+        let mut error = None;
+        let mut completed_day = None;
+        let mut schedule_ended = false;
+        let transformations = (self.black_and_white, self.mirrored);
+        if let Some(auto_step_data) = self.state.get_data_mut() {
+            if !auto_step_data.on_break {
+                auto_step_data.stats.poses += 1;
+                auto_step_data.remember_transformations(transformations);
+            }
+            let stepped = match step {
+                Step::Pose => auto_step_data.step_forward(),
+                Step::Block => auto_step_data.step_forward_block(),
+                Step::ToBlock(block) => {
+                    auto_step_data.start_block(block.min(auto_step_data.schedule.len() - 1));
+                    true
+                }
+            };
+            if !stepped {
+                schedule_ended = true;
+                // Taken so a looping session only completes the day once.
+                completed_day = auto_step_data.practice_day.take();
+                match self.config.end_policy {
+                    EndPolicy::Stop => finished = true,
+                    EndPolicy::Loop => auto_step_data.start_block(0),
+                    EndPolicy::RepeatLastBlock => {
+                        auto_step_data.start_block(auto_step_data.schedule.len() - 1)
+                    }
+                    EndPolicy::Pause => {
+                        auto_step_data.start_block(0);
+                        pause = true;
+                    }
+                }
+            }
+            if !finished {
+                if !auto_step_data.on_break && step == Step::Pose {
+                    replayed = auto_step_data.replay();
+                }
+                if !auto_step_data.on_break && replayed.is_none() {
+                    if !auto_step_data.pinned {
+                        match auto_step_data.set_pose_image(self.images_paths.as_slice()) {
+                            Ok(ran_out) => end = ran_out,
+                            Err(e) => error = Some(e),
+                        }
+                    }
+                    auto_step_data.pick_prompt();
+                    auto_step_data.push_history();
+                }
+                auto_step_data.time_left = Some(auto_step_data.get_current_duration() as f64);
+                if !auto_step_data.on_break {
+                    auto_step_data.start_lead_in(auto_step_data.lead_in_length);
+                }
+                pause |= auto_step_data.pauses_here();
+            }
+        }
+
+        if let Some(day) = completed_day {
+            self.progress.complete(day);
+        }
+        if schedule_ended {
+            self.schedule_ends += 1;
+        }
+        if finished {
+            self.summary = self.state.get_data().map(|data| data.stats.clone());
+            self.stop();
+            return;
+        }
+        if let Some(error) = error {
+            self.stop();
+            self.error = Some(error.to_string());
+            return;
+        }
+
+        if end {
+            self.prepare_images(false);
+        }
+
+        match replayed {
+            Some(transformations) => self.restore_transformations(transformations),
+            None => self.reset_transformations(),
+        }
+
+        if pause {
+            self.pause();
+        }
+    }
+
+    /// Whether the timer should show the end-of-pose warning right now,
+    /// taking blinking into account.
+    pub fn timer_warning(&self) -> bool {
+        let timer = &self.config.timer;
+        let time_left = match self.state.get_data() {
+            Some(data) if !data.on_break && data.lead_in.is_none() => data.time_left,
+            _ => None,
+        };
+
+        match time_left {
+            Some(left) if left <= timer.warning_seconds as f64 => {
+                !timer.flash || ((left * 2.).ceil() as usize).is_multiple_of(2)
+            }
+            _ => false,
+        }
+    }
+
+    /// The images the next poses will show, as many as the preview is set to.
+    pub fn upcoming(&self) -> Vec<PathBuf> {
+        let count = self.config.preview;
+        match self.state.get_data() {
+            Some(data) => data.upcoming(&self.images_paths, count),
+            None => vec![],
+        }
+    }
+
+    /// Swaps the `id`th upcoming image out, shuffling it back into the rest
+    /// of the queue.
+    pub fn requeue_upcoming(&mut self, id: usize) {
+        let keep = self.config.preview;
+        let auto_step_data = match self.state.get_data_mut() {
+            Some(data) => data,
+            None => return,
+        };
+
+        if let Some(pool) = auto_step_data.current_pool() {
+            if let Some(pool) = Arc::make_mut(&mut auto_step_data.pools).get_mut(&pool) {
+                pool.requeue(id, keep);
+            }
+        } else {
+            let next = auto_step_data.next_image_id();
+            requeue(
+                Arc::make_mut(&mut self.images_paths),
+                next + id,
+                next + keep,
+            );
+        }
+    }
+
+    /// Loads the first uncompleted curriculum day as the schedule. Returns
+    /// `false` if there is no curriculum or every day is done.
+    pub fn load_todays_practice(&mut self) -> bool {
+        let curriculum = match &self.curriculum {
+            Some(curriculum) => curriculum,
+            None => return false,
+        };
+
+        match self.progress.today(curriculum) {
+            Some(day) => {
+                let schedule = curriculum.days[day].practice_schedule();
+                self.config.override_schedule(schedule.clone());
+                self.practice = Some(Practice { day, schedule });
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if let AutoStepState::Playing(auto_step_data) = &self.state {
+            self.state = AutoStepState::Paused(auto_step_data.clone());
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.state = AutoStepState::Stopped;
+        self.prepare_images(false);
+        self.reset_transformations();
+    }
+
+    /// Returns to the previous pose in the session history.
+    pub fn back(&mut self) {
+        self.step_history(-1);
+    }
+
+    /// Moves `offset` poses through the session history, restoring the
+    /// schedule position and transformations of the pose. Returns `false`
+    /// without moving if the history doesn't reach that far.
+    fn step_history(&mut self, offset: isize) -> bool {
+        let transformations = (self.black_and_white, self.mirrored);
+        let restored = self
+            .state
+            .get_data_mut()
+            .and_then(|auto_step_data| auto_step_data.step_history(offset, transformations));
+
+        match restored {
+            Some(transformations) => {
+                self.restore_transformations(transformations);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Applies the transformations a pose was left with.
+    fn restore_transformations(&mut self, (black_and_white, mirrored): (bool, bool)) {
+        self.black_and_white = black_and_white;
+        self.mirrored = mirrored;
+        if let Some(auto_step_data) = self.state.get_data_mut() {
+            auto_step_data.restore_image(black_and_white, mirrored);
+        }
+    }
+
+    /// Keeps or stops keeping the current image for the following poses.
+    pub fn toggle_pin(&mut self) {
+        if let Some(auto_step_data) = self.state.get_data_mut() {
+            auto_step_data.pinned = !auto_step_data.pinned;
+        }
+    }
+
+    /// Lengthens or shortens the current pose by `seconds`.
+    pub fn adjust_time(&mut self, seconds: f64) {
+        if let Some(auto_step_data) = self.state.get_data_mut() {
+            auto_step_data.adjust_time(seconds);
+        }
+    }
+
+    /// Replaces the current image without moving through the schedule.
+    pub fn reload(&mut self) {
+        let mut end = false;
+        if let Some(auto_step_data) = self.state.get_data_mut() {
+            if auto_step_data.on_break {
+                return;
+            }
+            match auto_step_data.change_image(self.images_paths.as_slice()) {
+                Ok(ran_out) => end = ran_out,
+                Err(error) => {
+                    self.stop();
+                    self.error = Some(error.to_string());
+                    return;
+                }
+            }
+            auto_step_data.time_left = Some(auto_step_data.get_current_duration() as f64);
+            auto_step_data.update_history();
+        }
+
+        if end {
+            self.prepare_images(false);
+        }
+
+        self.reset_transformations();
+    }
+
+    /// Sets the transformations back to the current block's defaults and
+    /// applies them to the image.
     pub fn reset_transformations(&mut self) {
-        self.black_and_white = false;
-        self.mirrored = false;
+        let (black_and_white, mirrored) = self
+            .state
+            .get_data()
+            .map_or((false, false), AutoStepData::default_transformations);
+        self.black_and_white = black_and_white;
+        self.mirrored = mirrored;
+
+        if let Some(auto_step_data) = self.state.get_data_mut() {
+            auto_step_data.restore_image(black_and_white, mirrored);
+        }
     }
 
     pub fn prepare_images(&mut self, reload: bool) {
-        let image_exts = ["gif", "jpg", "jpeg", "png", "bmp"];
-
         if reload {
             if let Some(dir_path) = (*self.config.current_directory).clone() {
-                let images_paths: Vec<_> = fs::read_dir(dir_path.as_path())
-                    .expect("Unable to open chosen directory")
-                    .into_iter()
-                    .filter(|r| r.is_ok())
-                    .map(|r| r.unwrap().path())
-                    .filter(|r| {
-                        r.extension()
-                            .map_or(false, |ext| image_exts.contains(&ext.to_str().unwrap()))
-                    })
-                    .collect();
+                let images_paths = scan_images(&dir_path, self.config.scan_subfolders)
+                    .expect("Unable to open chosen directory");
 
+                self.image_meta = Arc::new(load_meta(&dir_path, &images_paths));
                 self.images_paths = Arc::new(images_paths);
             }
         }
@@ -131,6 +590,14 @@ impl ProgramData {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Step {
+    Pose,
+    Block,
+    /// Jump to the start of the given block.
+    ToBlock(usize),
+}
+
 #[derive(Clone, Data)]
 pub enum AutoStepState {
     Stopped,
@@ -177,37 +644,188 @@ fn to_image_crate_image(image: Arc<ImageBuf>) -> DynamicImage {
         _ => panic!("Unrecognized image format: {:#?}", image.format()),
     }
 }
+/// Reasons a session can't be started or carried on.
+#[derive(Debug)]
+pub enum StartError {
+    NoDirectory,
+    NoImages,
+    EmptyPool(String),
+    Schedule(ScheduleError),
+    Image(PathBuf),
+}
+
+impl fmt::Display for StartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartError::NoDirectory => write!(f, "Choose an images directory in the Config tab"),
+            StartError::NoImages => write!(f, "No images found in the chosen directory"),
+            StartError::EmptyPool(pool) => {
+                write!(
+                    f,
+                    "No images are tagged or in a folder named \"{}\" (folders are only \
+                     searched with \"Include subfolders\" on)",
+                    pool
+                )
+            }
+            StartError::Schedule(error) => write!(f, "Can't use this schedule: {}", error),
+            StartError::Image(path) => write!(f, "Unable to open {}", path.display()),
+        }
+    }
+}
+
+/// Number of poses the session history keeps.
+const HISTORY_LIMIT: usize = 200;
+
+/// A pose shown during the session, kept so the player can go back to it.
+#[derive(Clone)]
+struct HistoryEntry {
+    path: Arc<PathBuf>,
+    current: (usize, usize),
+    image_hold: usize,
+    prompt: Option<String>,
+    black_and_white: bool,
+    mirrored: bool,
+}
+
+#[derive(Clone, Debug, Default, Data, Lens)]
+pub struct SessionStats {
+    /// Number of poses shown, including skipped ones.
+    pub poses: usize,
+    /// Seconds spent drawing.
+    pub drawing_time: f64,
+    /// Seconds spent on breaks.
+    pub break_time: f64,
+    /// Seconds added to poses on the fly, negative if more were taken away.
+    pub time_adjustment: f64,
+}
+
 #[derive(Clone, Data, Lens)]
 pub struct AutoStepData {
-    pub current_image_id: usize,
+    pub schedule: ValidSchedule,
+    /// Position in `images_paths` of the last image drawn from it, `None`
+    /// while only pools have been drawn from.
+    pub current_image_id: Option<usize>,
     pub current_image: Arc<ImageBuf>,
     pub unmodified_image: Arc<ImageBuf>,
     pub current: (usize, usize),
+    /// Whether the session is resting after the block in `current`.
+    pub on_break: bool,
+    /// Number of upcoming poses that keep the current image.
+    pub image_hold: usize,
     pub time_left: Option<f64>,
+    /// Seconds of the countdown before the current pose starts.
+    pub lead_in: Option<f64>,
+    lead_in_length: usize,
+    pub stats: SessionStats,
+    /// Prompt of the current pose, picked from the block's prompts.
+    pub prompt: Option<String>,
+    /// Curriculum day the session counts towards.
+    pub practice_day: Option<usize>,
+    /// Keep the current image when the session moves on to the next pose.
+    pub pinned: bool,
+    current_path: Arc<PathBuf>,
+    /// Poses shown so far, oldest first.
+    history: Arc<Vec<HistoryEntry>>,
+    /// Position of the current pose in `history`.
+    history_pos: usize,
+    image_meta: Arc<HashMap<PathBuf, ImageMeta>>,
+    duration_policy: DurationPolicy,
+    /// Image queues of the blocks that have a pool, by pool name.
+    pools: Arc<HashMap<String, ImagePool>>,
 }
 
 impl AutoStepData {
-    pub fn new(data: &ProgramData) -> Self {
-        let id = 0;
-        let image = Arc::new(ImageBuf::from_file(&data.images_paths[id]).unwrap());
-        AutoStepData {
-            current_image_id: id,
+    pub fn new(data: &ProgramData) -> Result<Self, StartError> {
+        if data.config.current_directory.is_none() {
+            return Err(StartError::NoDirectory);
+        }
+        if data.images_paths.is_empty() {
+            return Err(StartError::NoImages);
+        }
+        let schedule = data
+            .config
+            .schedule
+            .validate()
+            .map_err(StartError::Schedule)?;
+
+        let root = data.config.current_directory.as_ref().as_ref().unwrap();
+        let mut pools = HashMap::new();
+        for pool in schedule
+            .blocks
+            .iter()
+            .filter_map(|b| b.options.pool.as_ref())
+        {
+            let paths = data
+                .images_paths
+                .iter()
+                .filter(|path| in_pool(root, path, data.image_meta.get(*path), pool))
+                .cloned()
+                .collect();
+            let image_pool = ImagePool::new(paths);
+            if image_pool.is_empty() {
+                return Err(StartError::EmptyPool(pool.clone()));
+            }
+            pools.insert(pool.clone(), image_pool);
+        }
+
+        let image = Arc::new(ImageBuf::empty());
+        let mut auto_step_data = AutoStepData {
+            current_image_id: None,
             current_image: image.clone(),
             unmodified_image: image,
             current: (0, 0),
-            time_left: Some(data.config.schedule[0].1 as f64),
-        }
+            on_break: false,
+            image_hold: 0,
+            time_left: None,
+            lead_in: None,
+            lead_in_length: data.config.lead_in.every_pose,
+            stats: SessionStats::default(),
+            prompt: None,
+            pinned: false,
+            practice_day: data
+                .practice
+                .as_ref()
+                .filter(|practice| practice.schedule.same(&data.config.schedule))
+                .map(|practice| practice.day),
+            schedule,
+            current_path: Arc::new(PathBuf::new()),
+            history: Arc::new(vec![]),
+            history_pos: 0,
+            image_meta: data.image_meta.clone(),
+            duration_policy: data.config.duration_policy,
+            pools: Arc::new(pools),
+        };
+        // Images that can't be opened are skipped like during the session.
+        auto_step_data.set_next_image(&data.images_paths)?;
+        auto_step_data.image_hold = auto_step_data.hold_for_current_pose();
+        auto_step_data.pick_prompt();
+        auto_step_data.push_history();
+        auto_step_data.time_left = Some(auto_step_data.get_current_duration() as f64);
+        auto_step_data.start_lead_in(
+            data.config
+                .lead_in
+                .session_start
+                .max(data.config.lead_in.every_pose),
+        );
+        Ok(auto_step_data)
     }
 
-    pub fn set_image_from_path(&mut self, path: &PathBuf) {
-        let image = Arc::new(ImageBuf::from_file(path).unwrap());
+    pub fn set_image_from_path(&mut self, path: &PathBuf) -> Result<(), StartError> {
+        let image =
+            Arc::new(ImageBuf::from_file(path).map_err(|_| StartError::Image(path.clone()))?);
         self.current_image = image.clone();
         self.unmodified_image = image;
+        self.current_path = Arc::new(path.clone());
+        Ok(())
     }
 
-    pub fn set_image_id(&mut self, images_paths: &[PathBuf], id: usize) {
-        self.current_image_id = id;
-        self.set_image_from_path(&images_paths[id]);
+    pub fn set_image_id(&mut self, images_paths: &[PathBuf], id: usize) -> Result<(), StartError> {
+        self.current_image_id = Some(id);
+        self.set_image_from_path(&images_paths[id])
+    }
+
+    fn next_image_id(&self) -> usize {
+        self.current_image_id.map_or(0, |id| id + 1)
     }
 
     pub fn restore_image(&mut self, bw: bool, mirror: bool) {
@@ -246,45 +864,544 @@ impl AutoStepData {
         ));
     }
 
-    pub fn set_next_image(&mut self, images_paths: &[PathBuf]) -> bool {
+    fn current_pool(&self) -> Option<String> {
+        self.schedule.block(self.current.0).options.pool.clone()
+    }
+
+    /// The next `count` images of the current block's pool or of
+    /// `images_paths`.
+    pub fn upcoming(&self, images_paths: &[PathBuf], count: usize) -> Vec<PathBuf> {
+        match self.current_pool() {
+            Some(pool) => self
+                .pools
+                .get(&pool)
+                .map_or(vec![], |pool| pool.upcoming(count)),
+            None => images_paths
+                .iter()
+                .skip(self.next_image_id())
+                .take(count)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Moves to the next image of the current block's pool, or of
+    /// `images_paths` if it has none, skipping images that can't be opened.
+    /// Returns `true` when `images_paths` ran out, pools are reshuffled on
+    /// their own. Fails if none of the images can be opened.
+    pub fn set_next_image(&mut self, images_paths: &[PathBuf]) -> Result<bool, StartError> {
+        if let Some(pool) = self.current_pool() {
+            let mut result = Err(StartError::EmptyPool(pool.clone()));
+            let tries = self.pools.get(&pool).map_or(0, ImagePool::len);
+            for _ in 0..tries {
+                let path = Arc::make_mut(&mut self.pools)
+                    .get_mut(&pool)
+                    .and_then(ImagePool::next_image);
+                if let Some(path) = path {
+                    result = self.set_image_from_path(&path).map(|_| false);
+                }
+                if result.is_ok() {
+                    break;
+                }
+            }
+            return result;
+        }
+
+        if images_paths.is_empty() {
+            return Err(StartError::NoImages);
+        }
+
         let mut end = false;
-        let id = self.current_image_id;
-        if id < images_paths.len() - 1 {
-            self.set_image_id(images_paths, id + 1);
+        let mut result = Err(StartError::NoImages);
+        for _ in 0..images_paths.len() {
+            let id = self.next_image_id();
+            result = if id < images_paths.len() {
+                self.set_image_id(images_paths, id)
+            } else {
+                end = true;
+                self.set_image_id(images_paths, 0)
+            };
+            if result.is_ok() {
+                break;
+            }
+        }
+
+        result.map(|_| end)
+    }
+
+    /// Sets up the image for a new pose, keeping the current one while it is
+    /// held. Returns `true` when the images ran out.
+    pub fn set_pose_image(&mut self, images_paths: &[PathBuf]) -> Result<bool, StartError> {
+        if self.image_hold > 0 {
+            self.image_hold -= 1;
+            self.restore_image(false, false);
+            Ok(false)
         } else {
-            self.set_image_id(images_paths, 0);
-            end = true;
+            self.change_image(images_paths)
         }
+    }
 
-        end
+    /// Moves to the next image, which is then held for as many poses as the
+    /// current block asks for. Returns `true` when the images ran out.
+    pub fn change_image(&mut self, images_paths: &[PathBuf]) -> Result<bool, StartError> {
+        let end = self.set_next_image(images_paths)?;
+        self.image_hold = self.hold_for_current_pose();
+        Ok(end)
     }
 
-    pub fn step_forward(&mut self, schedule: &[(usize, usize)]) {
+    fn hold_for_current_pose(&self) -> usize {
         let (big_step, small_step) = self.current;
+        let block = self.schedule.block(big_step);
+
+        if block.options.repeat_image {
+            block.options.hold_image.max(block.count - 1 - small_step)
+        } else {
+            block.options.hold_image
+        }
+    }
 
-        let current_big_step_length = schedule[big_step].0;
-        self.current = if small_step >= current_big_step_length - 1 {
-            if big_step >= schedule.len() - 1 {
-                (0, 0)
+    fn start_lead_in(&mut self, length: usize) {
+        self.lead_in = Some(length as f64).filter(|&length| length > 0.);
+    }
+
+    /// Counts down the lead-in, or the current pose or break once it is over,
+    /// by `elapsed`.
+    pub fn tick(&mut self, elapsed: Duration) {
+        if let Some(lead_in) = self.lead_in {
+            self.lead_in = Duration::from_secs_f64(lead_in)
+                .checked_sub(elapsed)
+                .map(|d| d.as_secs_f64());
+        } else if let Some(time_left) = self.time_left {
+            let spent = elapsed.as_secs_f64().min(time_left);
+            if self.on_break {
+                self.stats.break_time += spent;
             } else {
-                (big_step + 1, 0)
+                self.stats.drawing_time += spent;
             }
+
+            self.time_left = Duration::from_secs_f64(time_left)
+                .checked_sub(elapsed)
+                .map(|d| d.as_secs_f64());
+        }
+    }
+
+    /// Seconds of the schedule behind the session, counting the current pose
+    /// or break up to now.
+    pub fn elapsed(&self) -> f64 {
+        let (big_step, small_step) = self.current;
+        let block = self.schedule.block(big_step);
+        let before: usize = self.schedule.blocks[..big_step]
+            .iter()
+            .map(ScheduleBlock::total_duration)
+            .sum();
+        let in_block = if self.on_break {
+            block.count * block.duration
         } else {
-            (big_step, small_step + 1)
+            small_step * block.duration
         };
+        let current = self.get_current_duration() as f64 - self.time_left.unwrap_or(0.);
+
+        (before + in_block) as f64 + current.max(0.)
     }
 
-    pub fn step_forward_block(&mut self, schedule: &[(usize, usize)]) {
+    /// Part of the current pose or break that has passed, from 0 to 1.
+    pub fn progress(&self) -> f64 {
+        let duration = self.get_current_duration() as f64;
+        match self.time_left {
+            Some(left) if duration > 0. => (1. - left / duration).clamp(0., 1.),
+            _ => 1.,
+        }
+    }
+
+    /// Lengthens or shortens the current pose by `seconds`.
+    pub fn adjust_time(&mut self, seconds: f64) {
+        if self.on_break {
+            return;
+        }
+        if let Some(time_left) = self.time_left {
+            let adjusted = (time_left + seconds).max(0.);
+            self.stats.time_adjustment += adjusted - time_left;
+            self.time_left = Some(adjusted);
+        }
+    }
+
+    /// Moves to the next pose, going through the break after the block if
+    /// there is one. Returns `false` without moving if the schedule is over.
+    pub fn step_forward(&mut self) -> bool {
+        let (big_step, small_step) = self.current;
+        let block = self.schedule.block(big_step);
+
+        if !self.on_break && small_step >= block.count - 1 && block.break_after.is_some() {
+            self.on_break = true;
+        } else if self.on_break || small_step >= block.count - 1 {
+            return self.step_to_next_block();
+        } else {
+            self.current = (big_step, small_step + 1);
+        }
+        true
+    }
+
+    /// Moves to the break after the current block, or straight to the next
+    /// block if there is no break or the session is already resting.
+    /// Returns `false` without moving if the schedule is over.
+    pub fn step_forward_block(&mut self) -> bool {
+        let block = self.schedule.block(self.current.0);
+
+        if !self.on_break && block.break_after.is_some() {
+            self.on_break = true;
+            true
+        } else {
+            self.step_to_next_block()
+        }
+    }
+
+    fn step_to_next_block(&mut self) -> bool {
         let (big_step, _) = self.current;
 
-        self.current = if big_step >= schedule.len() - 1 {
-            (0, 0)
+        if big_step >= self.schedule.len() - 1 {
+            false
         } else {
-            (big_step + 1, 0)
+            self.start_block(big_step + 1);
+            true
+        }
+    }
+
+    pub fn start_block(&mut self, block: usize) {
+        self.current = (block, 0);
+        self.on_break = false;
+    }
+
+    fn history_entry(&self) -> HistoryEntry {
+        let (black_and_white, mirrored) = self.default_transformations();
+        HistoryEntry {
+            path: self.current_path.clone(),
+            current: self.current,
+            image_hold: self.image_hold,
+            prompt: self.prompt.clone(),
+            black_and_white,
+            mirrored,
+        }
+    }
+
+    /// Records the current pose at the end of the history, dropping the
+    /// poses that were gone back over.
+    fn push_history(&mut self) {
+        let entry = self.history_entry();
+        let history = Arc::make_mut(&mut self.history);
+        history.truncate(self.history_pos + 1);
+        history.push(entry);
+        if history.len() > HISTORY_LIMIT {
+            history.remove(0);
+        }
+        self.history_pos = history.len() - 1;
+    }
+
+    /// Records a new image for the current pose.
+    fn update_history(&mut self) {
+        let entry = self.history_entry();
+        if let Some(old) = Arc::make_mut(&mut self.history).get_mut(self.history_pos) {
+            *old = entry;
+        }
+    }
+
+    /// Stores the transformations the current pose is left with.
+    fn remember_transformations(&mut self, (black_and_white, mirrored): (bool, bool)) {
+        if let Some(entry) = Arc::make_mut(&mut self.history).get_mut(self.history_pos) {
+            entry.black_and_white = black_and_white;
+            entry.mirrored = mirrored;
+        }
+    }
+
+    /// Moves `offset` poses through the history and returns the restored
+    /// transformations. Going back from a break returns to the pose before it.
+    fn step_history(
+        &mut self,
+        offset: isize,
+        transformations: (bool, bool),
+    ) -> Option<(bool, bool)> {
+        let target = if self.on_break && offset < 0 {
+            self.history_pos
+        } else {
+            self.history_pos.checked_add_signed(offset)?
         };
+        self.history.get(target)?;
+
+        if !self.on_break {
+            self.remember_transformations(transformations);
+        }
+        self.restore_history(target)
+    }
+
+    /// Shows the pose after the current one in the history again, if the
+    /// session went back, and returns its transformations. The session has
+    /// to be at that pose's place in the schedule already, so replaying goes
+    /// through breaks, lead-ins and the pose count like new poses do.
+    fn replay(&mut self) -> Option<(bool, bool)> {
+        self.restore_history(self.history_pos + 1)
+    }
+
+    /// Restores the pose at `target` in the history.
+    fn restore_history(&mut self, target: usize) -> Option<(bool, bool)> {
+        let entry = self.history.get(target)?.clone();
+
+        // An image that can no longer be opened is stepped over like the end
+        // of the history.
+        self.set_image_from_path(&entry.path).ok()?;
+        self.history_pos = target;
+        self.current = entry.current;
+        self.on_break = false;
+        self.image_hold = entry.image_hold;
+        self.prompt = entry.prompt;
+        self.time_left = Some(self.get_current_duration() as f64);
+
+        Some((entry.black_and_white, entry.mirrored))
     }
 
-    pub fn get_current_duration(&self, schedule: &[(usize, usize)]) -> usize {
-        schedule[self.current.0].1
+    fn pick_prompt(&mut self) {
+        let prompts = &self.schedule.block(self.current.0).options.prompts;
+        self.prompt = prompts.choose(&mut thread_rng()).cloned();
+    }
+
+    /// Text shown over the current pose: the block's instruction and the
+    /// pose's prompt, whichever are set.
+    pub fn caption(&self) -> String {
+        let instruction = &self.schedule.block(self.current.0).instruction;
+        [instruction.as_deref(), self.prompt.as_deref()]
+            .iter()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>()
+            .join(": ")
+    }
+
+    /// Black and white and mirroring the current block starts its poses with.
+    pub fn default_transformations(&self) -> (bool, bool) {
+        let options = &self.schedule.block(self.current.0).options;
+        (options.black_and_white, options.mirrored)
+    }
+
+    /// Whether the session has just entered a block that asks to be paused.
+    pub fn pauses_here(&self) -> bool {
+        !self.on_break
+            && self.current.1 == 0
+            && self.schedule.block(self.current.0).options.pause_before
+    }
+
+    /// Length of the current break, or of the current pose once the image's
+    /// metadata is applied.
+    pub fn get_current_duration(&self) -> usize {
+        let block = self.schedule.block(self.current.0);
+
+        if self.on_break {
+            block.break_after.unwrap_or(0)
+        } else {
+            match self.image_meta.get(&*self.current_path) {
+                Some(meta) => self.duration_policy.apply(block.duration, meta),
+                None => block.duration,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl ProgramData {
+    /// Program state with the default config and the given images, without
+    /// reading or writing any of the user's files.
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub fn for_tests(dir: &Path, images_paths: Vec<PathBuf>) -> Self {
+        let config = Config {
+            current_directory: Arc::new(Some(dir.to_owned())),
+            ..Config::default()
+        };
+        ProgramData {
+            images_paths: Arc::new(images_paths),
+            image_meta: Arc::new(HashMap::new()),
+            config,
+            presets: Presets {
+                list: Arc::new(vec![]),
+            },
+            curriculum: None,
+            progress: Progress::default(),
+            practice: None,
+            state: AutoStepState::Stopped,
+            rng: Arc::new(RwLock::new(thread_rng())),
+            black_and_white: false,
+            mirrored: false,
+            error: None,
+            notation_error: None,
+            share_status: None,
+            summary: None,
+            schedule_ends: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::curriculum::CurriculumDay;
+    use druid::image::{Rgb, RgbImage};
+    use std::fs;
+
+    /// An empty directory for the test called `name`.
+    pub fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("art_practice_{}_{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    pub fn write_image(path: &Path) {
+        RgbImage::from_pixel(2, 2, Rgb([0, 0, 0]))
+            .save(path)
+            .unwrap();
+    }
+
+    fn curriculum() -> Curriculum {
+        let day = |count| CurriculumDay {
+            name: String::new(),
+            schedule: Schedule::new(vec![ScheduleBlock::new(count, 30)]),
+            filter: None,
+            notes: String::new(),
+        };
+        Curriculum {
+            name: "Test".to_owned(),
+            days: Arc::new(vec![day(1), day(2)]),
+        }
+    }
+
+    #[test]
+    fn todays_practice_is_not_saved_as_the_schedule() {
+        let dir = test_dir("todays_practice_is_not_saved_as_the_schedule");
+        let mut data = ProgramData::for_tests(&dir, vec![]);
+        let own = data.config.schedule.clone();
+        data.curriculum = Some(curriculum());
+
+        assert!(data.load_todays_practice());
+        assert_eq!(
+            *data.config.schedule.blocks,
+            vec![ScheduleBlock::new(1, 30)]
+        );
+        assert!(data.config.saved_schedule().same(&own));
+
+        // Loading the next day still keeps the user's own schedule.
+        data.progress.complete(0);
+        assert!(data.load_todays_practice());
+        assert_eq!(
+            *data.config.schedule.blocks,
+            vec![ScheduleBlock::new(2, 30)]
+        );
+        assert!(data.config.saved_schedule().same(&own));
+
+        // Editing the day's schedule makes it the one that is saved.
+        data.config.schedule = data.config.schedule.clone().keep_options_from(&own);
+        assert!(data.config.saved_schedule().same(&data.config.schedule));
+    }
+
+    /// Starts a session on `data` as the Play button does.
+    pub fn start(data: &mut ProgramData) {
+        let auto_step_data = AutoStepData::new(data).unwrap();
+        data.state = AutoStepState::Playing(auto_step_data);
+        data.reset_transformations();
+    }
+
+    /// Program state with one readable image.
+    pub fn data_with_image(test: &str) -> ProgramData {
+        let dir = test_dir(test);
+        let image = dir.join("image.png");
+        write_image(&image);
+        ProgramData::for_tests(&dir, vec![image])
+    }
+
+    #[test]
+    fn practice_day_is_completed_once_under_every_end_policy() {
+        for &policy in [
+            EndPolicy::Stop,
+            EndPolicy::Loop,
+            EndPolicy::RepeatLastBlock,
+            EndPolicy::Pause,
+        ]
+        .iter()
+        {
+            let mut data = data_with_image("practice_day_is_completed_once");
+            data.config.end_policy = policy;
+            data.curriculum = Some(curriculum());
+            data.load_todays_practice();
+            start(&mut data);
+
+            for _ in 0..3 {
+                data.advance(Step::Pose);
+            }
+            assert_eq!(*data.progress.completed, vec![0], "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn replaying_the_history_goes_through_breaks() {
+        let dir = test_dir("replaying_the_history_goes_through_breaks");
+        let images: Vec<_> = (0..3).map(|i| dir.join(format!("{}.png", i))).collect();
+        images.iter().for_each(|image| write_image(image));
+        let mut data = ProgramData::for_tests(&dir, images.clone());
+        data.config.schedule = Schedule::new(vec![
+            ScheduleBlock::new(1, 30).with_break(10),
+            ScheduleBlock::new(1, 30),
+        ]);
+        data.config.lead_in.every_pose = 3;
+        start(&mut data);
+        data.advance(Step::Pose);
+        data.advance(Step::Pose);
+        data.back();
+
+        let auto_step_data = data.state.get_data().unwrap();
+        assert_eq!(
+            (auto_step_data.current, auto_step_data.on_break),
+            ((0, 0), false)
+        );
+        assert_eq!(*auto_step_data.current_path, images[0]);
+
+        data.advance(Step::Pose);
+        let auto_step_data = data.state.get_data().unwrap();
+        assert!(auto_step_data.on_break);
+        assert_eq!(auto_step_data.time_left, Some(10.));
+
+        data.advance(Step::Pose);
+        let auto_step_data = data.state.get_data().unwrap();
+        assert_eq!(
+            (auto_step_data.current, auto_step_data.on_break),
+            ((1, 0), false)
+        );
+        assert_eq!(*auto_step_data.current_path, images[1]);
+        assert_eq!(auto_step_data.lead_in, Some(3.));
+        assert_eq!(auto_step_data.stats.poses, 2);
+    }
+
+    #[test]
+    fn start_skips_unreadable_images() {
+        let dir = test_dir("start_skips_unreadable_images");
+        let corrupt = dir.join("corrupt.png");
+        fs::write(&corrupt, b"not an image").unwrap();
+        let valid = dir.join("valid.png");
+        write_image(&valid);
+
+        let data = ProgramData::for_tests(&dir, vec![corrupt, valid.clone()]);
+        let auto_step_data = AutoStepData::new(&data).unwrap();
+
+        assert_eq!(*auto_step_data.current_path, valid);
+        assert_eq!(auto_step_data.current_image_id, Some(1));
+    }
+
+    #[test]
+    fn start_fails_when_no_image_can_be_read() {
+        let dir = test_dir("start_fails_when_no_image_can_be_read");
+        let corrupt = dir.join("corrupt.png");
+        fs::write(&corrupt, b"not an image").unwrap();
+
+        let data = ProgramData::for_tests(&dir, vec![corrupt]);
+
+        assert!(matches!(
+            AutoStepData::new(&data),
+            Err(StartError::Image(_))
+        ));
     }
 }
@@ -1,8 +1,9 @@
-use druid::{commands, AppDelegate, Command, DelegateCtx, Env, Handled, Target};
+use druid::{commands, AppDelegate, Application, Command, DelegateCtx, Env, Handled, Target};
 
 use std::sync::Arc;
 
 use crate::data::*;
+use crate::share;
 
 pub struct Delegate;
 
@@ -15,8 +16,19 @@ impl AppDelegate<ProgramData> for Delegate {
         data: &mut ProgramData,
         _env: &Env,
     ) -> Handled {
-
         if let Some(file_info) = cmd.get(commands::OPEN_FILE) {
+            // Only the schedule import dialog picks files.
+            if !file_info.path().is_dir() {
+                data.share_status = Some(match share::import_file(file_info.path()) {
+                    Ok(schedule) => {
+                        data.config.schedule = schedule;
+                        format!("Imported {}", file_info.path().display())
+                    }
+                    Err(error) => format!("Unable to import: {}", error),
+                });
+                return Handled::Yes;
+            }
+
             data.config.current_directory = Arc::new(Some(file_info.path().to_path_buf()));
 
             data.prepare_images(true);
@@ -25,6 +37,63 @@ impl AppDelegate<ProgramData> for Delegate {
 
             return Handled::Yes;
         }
+        if let Some(file_info) = cmd.get(commands::SAVE_FILE_AS) {
+            data.share_status = Some(
+                match share::export_file(file_info.path(), &data.config.schedule) {
+                    Ok(()) => format!("Exported {}", file_info.path().display()),
+                    Err(error) => format!("Unable to export: {}", error),
+                },
+            );
+            return Handled::Yes;
+        }
+        if cmd.is(COPY_SHARE_CODE) {
+            let code = share::encode(&data.config.schedule);
+            Application::global().clipboard().put_string(code);
+            data.share_status = Some("Share code copied".to_owned());
+            return Handled::Yes;
+        }
+        if cmd.is(IMPORT_SHARE_CODE) {
+            let code = Application::global().clipboard().get_string();
+            data.share_status = Some(match code.as_deref().map(share::decode) {
+                Some(Ok(schedule)) => {
+                    data.config.schedule = schedule;
+                    "Imported the share code".to_owned()
+                }
+                Some(Err(error)) => format!("Unable to import: {}", error),
+                None => "Copy a share code first".to_owned(),
+            });
+            return Handled::Yes;
+        }
+        if let Some(block) = cmd.get(JUMP_TO_BLOCK) {
+            data.advance(Step::ToBlock(*block));
+            return Handled::Yes;
+        }
+        if let Some(id) = cmd.get(REQUEUE_UPCOMING) {
+            data.requeue_upcoming(*id);
+            return Handled::Yes;
+        }
+        if let Some(error) = cmd.get(SET_NOTATION_ERROR) {
+            data.notation_error = error.clone();
+            return Handled::Yes;
+        }
+        if let Some(id) = cmd.get(LOAD_PRESET) {
+            if let Some(preset) = data.presets.get(*id) {
+                data.config.schedule = preset.schedule.clone();
+            }
+            return Handled::Yes;
+        }
+        if cmd.is(SAVE_PRESET) {
+            data.presets.add("New preset", data.config.schedule.clone());
+            return Handled::Yes;
+        }
+        if let Some(id) = cmd.get(DUPLICATE_PRESET) {
+            data.presets.duplicate(*id);
+            return Handled::Yes;
+        }
+        if let Some(id) = cmd.get(DELETE_PRESET) {
+            data.presets.delete(*id);
+            return Handled::Yes;
+        }
         Handled::No
     }
 }
@@ -0,0 +1,444 @@
+use druid::piet::ImageFormat;
+use druid::{Data, ImageBuf};
+
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng};
+use ron::extensions::Extensions;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+const IMAGE_EXTS: [&str; 5] = ["gif", "jpg", "jpeg", "png", "bmp"];
+
+/// Longest side of preview thumbnails in pixels.
+const THUMBNAIL_SIZE: u32 = 160;
+/// Number of thumbnails kept before the cache drops the ones not in use.
+const THUMBNAIL_CACHE_LIMIT: usize = 64;
+
+/// File in the images directory listing metadata for several images.
+const PLAYLIST_FILE: &str = "playlist.ron";
+
+/// Extra information about an image, read from a sidecar file next to it
+/// named after the image with `.ron` appended, e.g. `scene.jpg.ron`:
+///
+/// ```ron
+/// (duration: 300, tags: ["hands"])
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ImageMeta {
+    /// Seconds the image needs, used by `DurationPolicy::Replace` and `Cap`.
+    pub duration: Option<usize>,
+    /// Factor the block's pose length is multiplied by under
+    /// `DurationPolicy::Scale`.
+    pub scale: Option<f64>,
+    /// Pools the image belongs to besides the folders it is in.
+    pub tags: Vec<String>,
+}
+
+/// An entry of `playlist.ron`, which gives metadata for images by their path
+/// in the images directory:
+///
+/// ```ron
+/// [(image: "scenes/market.jpg", duration: 300), (image: "crowd.png", scale: 2.0)]
+/// ```
+#[derive(Deserialize)]
+struct PlaylistEntry {
+    image: PathBuf,
+    #[serde(default)]
+    duration: Option<usize>,
+    #[serde(default)]
+    scale: Option<f64>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// Lists the images in `dir`, and in its subfolders if `recursive`, skipping
+/// hidden ones. Symlinked folders aren't followed, so links can't make the
+/// scan loop.
+pub fn scan_images(dir: &Path, recursive: bool) -> io::Result<Vec<PathBuf>> {
+    let mut images = vec![];
+
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            if recursive {
+                images.extend(scan_images(&path, true).unwrap_or_default());
+            }
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| IMAGE_EXTS.contains(&ext.to_lowercase().as_str()))
+        {
+            images.push(path);
+        }
+    }
+
+    Ok(images)
+}
+
+pub fn sidecar_path(image: &Path) -> PathBuf {
+    let mut file_name = image.file_name().unwrap_or_default().to_owned();
+    file_name.push(".ron");
+    image.with_file_name(file_name)
+}
+
+/// Reads the playlist in `root` and the sidecar files of the images that
+/// have one. A sidecar file takes the place of the image's playlist entry.
+pub fn load_meta(root: &Path, images: &[PathBuf]) -> HashMap<PathBuf, ImageMeta> {
+    let options = ron::Options::default().with_default_extension(Extensions::IMPLICIT_SOME);
+
+    let playlist: Vec<PlaylistEntry> = File::open(root.join(PLAYLIST_FILE))
+        .ok()
+        .and_then(|file| options.from_reader(file).ok())
+        .unwrap_or_default();
+    let mut meta: HashMap<_, _> = playlist
+        .into_iter()
+        .map(|entry| {
+            let image_meta = ImageMeta {
+                duration: entry.duration,
+                scale: entry.scale,
+                tags: entry.tags,
+            };
+            (root.join(entry.image), image_meta)
+        })
+        .filter(|(image, _)| images.contains(image))
+        .collect();
+
+    for image in images {
+        let sidecar = File::open(sidecar_path(image))
+            .ok()
+            .and_then(|file| options.from_reader(file).ok());
+        if let Some(sidecar) = sidecar {
+            meta.insert(image.clone(), sidecar);
+        }
+    }
+
+    meta
+}
+
+/// How an image's metadata changes the pose length of the block.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Data, Serialize, Deserialize)]
+pub enum DurationPolicy {
+    /// The image's duration is used instead of the block's.
+    #[default]
+    Replace,
+    /// The block's pose length is multiplied by the image's scale.
+    Scale,
+    /// The image's duration is the longest the pose can last.
+    Cap,
+}
+
+impl DurationPolicy {
+    /// The pose length for an image with `meta` in a block whose poses last
+    /// `scheduled` seconds.
+    pub fn apply(self, scheduled: usize, meta: &ImageMeta) -> usize {
+        match self {
+            DurationPolicy::Replace => meta.duration.unwrap_or(scheduled),
+            DurationPolicy::Scale => meta.scale.map_or(scheduled, |scale| {
+                (scheduled as f64 * scale).round() as usize
+            }),
+            DurationPolicy::Cap => meta
+                .duration
+                .map_or(scheduled, |duration| scheduled.min(duration)),
+        }
+        .max(1)
+    }
+}
+
+/// Whether `image` belongs to `pool`, either by a tag in its sidecar file or
+/// by being in a subfolder of `root` with that path, like `hands` or
+/// `figures/hands`. Both are compared ignoring case.
+pub fn in_pool(root: &Path, image: &Path, meta: Option<&ImageMeta>, pool: &str) -> bool {
+    let tagged =
+        meta.is_some_and(|meta| meta.tags.iter().any(|tag| tag.eq_ignore_ascii_case(pool)));
+
+    let pool_folders: Vec<_> = pool.split(['/', '\\']).filter(|f| !f.is_empty()).collect();
+    let in_folder = image
+        .parent()
+        .and_then(|parent| parent.strip_prefix(root).ok())
+        .is_some_and(|folder| {
+            let folders: Vec<_> = folder.iter().map(|f| f.to_string_lossy()).collect();
+            folders.len() >= pool_folders.len()
+                && pool_folders
+                    .iter()
+                    .zip(&folders)
+                    .all(|(pool, folder)| folder.eq_ignore_ascii_case(pool))
+        });
+
+    tagged || in_folder
+}
+
+/// A shuffled queue of the images of one pool.
+#[derive(Clone, Data)]
+pub struct ImagePool {
+    paths: Arc<Vec<PathBuf>>,
+    next: usize,
+}
+
+impl ImagePool {
+    pub fn new(mut paths: Vec<PathBuf>) -> Self {
+        paths.shuffle(&mut thread_rng());
+        ImagePool {
+            paths: Arc::new(paths),
+            next: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// The images the next `count` draws will take, as far as the queue goes
+    /// before reshuffling.
+    pub fn upcoming(&self, count: usize) -> Vec<PathBuf> {
+        self.paths
+            .iter()
+            .skip(self.next)
+            .take(count)
+            .cloned()
+            .collect()
+    }
+
+    /// Puts the `id`th upcoming image back into the queue behind the first
+    /// `keep` upcoming ones.
+    pub fn requeue(&mut self, id: usize, keep: usize) {
+        requeue(
+            Arc::make_mut(&mut self.paths),
+            self.next + id,
+            self.next + keep,
+        );
+    }
+
+    /// Takes the next image, reshuffling once every image has been shown.
+    pub fn next_image(&mut self) -> Option<PathBuf> {
+        if self.next >= self.paths.len() {
+            Arc::make_mut(&mut self.paths).shuffle(&mut thread_rng());
+            self.next = 0;
+        }
+        let path = self.paths.get(self.next).cloned();
+        self.next += 1;
+        path
+    }
+}
+
+/// Moves the image at `from` to a random position at or after `after`.
+pub fn requeue(paths: &mut Vec<PathBuf>, from: usize, after: usize) {
+    if from >= paths.len() {
+        return;
+    }
+    let path = paths.remove(from);
+    let start = after.min(paths.len());
+    let to = thread_rng().gen_range(start..=paths.len());
+    paths.insert(to, path);
+}
+
+/// Thumbnails of images by path, so previews only decode an image once.
+/// Images are decoded on a background thread to keep the UI responsive.
+#[derive(Default)]
+pub struct ThumbnailCache {
+    thumbnails: HashMap<PathBuf, Option<ImageBuf>>,
+    /// Images being decoded.
+    pending: HashSet<PathBuf>,
+}
+
+impl ThumbnailCache {
+    /// Returns the thumbnail of `path`, or `None` if it isn't decoded yet or
+    /// can't be.
+    pub fn get(&self, path: &Path) -> Option<&ImageBuf> {
+        self.thumbnails.get(path).and_then(Option::as_ref)
+    }
+
+    /// Starts decoding the thumbnails of `paths` that aren't cached, calling
+    /// `done` from the decoding thread as each one is ready so it can be
+    /// passed to `insert`.
+    pub fn request<F>(&mut self, paths: &[PathBuf], done: F)
+    where
+        F: Fn(PathBuf, Option<ImageBuf>) + Send + 'static,
+    {
+        if self.thumbnails.len() >= THUMBNAIL_CACHE_LIMIT {
+            self.thumbnails.retain(|path, _| paths.contains(path));
+        }
+        let missing: Vec<_> = paths
+            .iter()
+            .filter(|path| !self.thumbnails.contains_key(*path) && !self.pending.contains(*path))
+            .cloned()
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+
+        self.pending.extend(missing.iter().cloned());
+        thread::spawn(move || {
+            for path in missing {
+                let thumbnail = decode_thumbnail(&path);
+                done(path, thumbnail);
+            }
+        });
+    }
+
+    pub fn insert(&mut self, path: PathBuf, thumbnail: Option<ImageBuf>) {
+        self.pending.remove(&path);
+        self.thumbnails.insert(path, thumbnail);
+    }
+}
+
+fn decode_thumbnail(path: &Path) -> Option<ImageBuf> {
+    let thumbnail = druid::image::open(path)
+        .ok()?
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .to_rgba8();
+    let (width, height) = thumbnail.dimensions();
+
+    Some(ImageBuf::from_raw(
+        thumbnail.into_raw(),
+        ImageFormat::RgbaSeparate,
+        width as usize,
+        height as usize,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::tests::test_dir;
+
+    fn meta(duration: Option<usize>, scale: Option<f64>) -> ImageMeta {
+        ImageMeta {
+            duration,
+            scale,
+            tags: vec![],
+        }
+    }
+
+    #[test]
+    fn replace_uses_the_image_duration() {
+        let policy = DurationPolicy::Replace;
+        assert_eq!(policy.apply(60, &meta(Some(300), None)), 300);
+        assert_eq!(policy.apply(60, &meta(Some(30), Some(2.))), 30);
+        assert_eq!(policy.apply(60, &meta(None, Some(2.))), 60);
+    }
+
+    #[test]
+    fn scale_multiplies_the_block_duration() {
+        let policy = DurationPolicy::Scale;
+        assert_eq!(policy.apply(60, &meta(None, Some(2.))), 120);
+        assert_eq!(policy.apply(45, &meta(None, Some(0.5))), 23);
+        assert_eq!(policy.apply(60, &meta(Some(300), None)), 60);
+        assert_eq!(policy.apply(60, &meta(None, Some(0.))), 1);
+    }
+
+    #[test]
+    fn cap_limits_the_block_duration() {
+        let policy = DurationPolicy::Cap;
+        assert_eq!(policy.apply(300, &meta(Some(60), None)), 60);
+        assert_eq!(policy.apply(30, &meta(Some(60), None)), 30);
+        assert_eq!(policy.apply(30, &meta(None, Some(2.))), 30);
+    }
+
+    fn sorted(mut paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn scan_only_recurses_when_asked() {
+        let dir = test_dir("scan_only_recurses_when_asked");
+        fs::create_dir_all(dir.join("figures").join("hands")).unwrap();
+        fs::create_dir(dir.join(".hidden")).unwrap();
+        for path in [
+            "top.JPG",
+            "notes.txt",
+            ".hidden.png",
+            "figures/hands/hand.png",
+            ".hidden/secret.png",
+        ] {
+            fs::write(dir.join(path), b"").unwrap();
+        }
+
+        assert_eq!(scan_images(&dir, false).unwrap(), vec![dir.join("top.JPG")]);
+        assert_eq!(
+            sorted(scan_images(&dir, true).unwrap()),
+            vec![dir.join("figures/hands/hand.png"), dir.join("top.JPG")]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scan_does_not_follow_symlinked_folders() {
+        let dir = test_dir("scan_does_not_follow_symlinked_folders");
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub").join("image.png"), b"").unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("sub").join("loop")).unwrap();
+
+        assert_eq!(
+            scan_images(&dir, true).unwrap(),
+            vec![dir.join("sub").join("image.png")]
+        );
+    }
+
+    #[test]
+    fn pools_match_tags_ignoring_case() {
+        let root = Path::new("/images");
+        let image = root.join("scene.png");
+        let tagged = ImageMeta {
+            tags: vec!["Hands".to_owned()],
+            ..ImageMeta::default()
+        };
+
+        assert!(in_pool(root, &image, Some(&tagged), "hands"));
+        assert!(in_pool(root, &image, Some(&tagged), "HANDS"));
+        assert!(!in_pool(root, &image, Some(&tagged), "feet"));
+        assert!(!in_pool(root, &image, None, "hands"));
+    }
+
+    #[test]
+    fn pools_match_folders_ignoring_case() {
+        let root = Path::new("/images");
+        let image = root.join("Figures").join("Hands").join("hand.png");
+
+        assert!(in_pool(root, &image, None, "figures"));
+        assert!(in_pool(root, &image, None, "figures/hands"));
+        assert!(in_pool(root, &image, None, "FIGURES/Hands/"));
+        assert!(!in_pool(root, &image, None, "hands"));
+        assert!(!in_pool(root, &image, None, "figures/hand"));
+        assert!(!in_pool(root, &image, None, "figures/hands/left"));
+        assert!(!in_pool(root, &root.join("hand.png"), None, "figures"));
+    }
+
+    #[test]
+    fn sidecars_take_the_place_of_playlist_entries() {
+        let dir = test_dir("sidecars_take_the_place_of_playlist_entries");
+        fs::create_dir(dir.join("scenes")).unwrap();
+        let market = dir.join("scenes").join("market.jpg");
+        let crowd = dir.join("crowd.png");
+        let unlisted = dir.join("unlisted.png");
+        fs::write(
+            dir.join(PLAYLIST_FILE),
+            r#"[
+                (image: "scenes/market.jpg", duration: 300),
+                (image: "crowd.png", scale: 2.0, tags: ["crowds"]),
+                (image: "missing.png", duration: 10),
+            ]"#,
+        )
+        .unwrap();
+        fs::write(sidecar_path(&crowd), "(duration: 90)").unwrap();
+
+        let meta = load_meta(&dir, &[market.clone(), crowd.clone(), unlisted]);
+
+        assert_eq!(meta.len(), 2);
+        assert_eq!(meta[&market].duration, Some(300));
+        assert_eq!(meta[&crowd], self::meta(Some(90), None));
+    }
+}
@@ -0,0 +1,152 @@
+//! Keyboard shortcuts for the Player tab.
+//!
+//! A binding is written as modifiers followed by a key, joined with `+`, like
+//! `Space`, `B` or `Ctrl+Shift+ArrowRight`. Named keys use their names from the
+//! UI Events spec (`ArrowLeft`, `Escape`, `PageUp`, ...). Case doesn't matter,
+//! and `Shift` is ignored for character keys since it already changes them.
+
+use druid::{Data, KbKey, KeyEvent, Lens};
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+
+const MODIFIERS: [&str; 4] = ["Ctrl", "Alt", "Meta", "Shift"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Data, Serialize, Deserialize)]
+pub enum Action {
+    PlayPause,
+    Reload,
+    Pin,
+    Back,
+    Skip,
+    SkipBlock,
+    AddHalfMinute,
+    AddMinute,
+    RemoveHalfMinute,
+    Stop,
+    BlackAndWhite,
+    Mirror,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Action::PlayPause => "Play/pause",
+            Action::Reload => "Reload",
+            Action::Pin => "Pin/unpin",
+            Action::Back => "Back",
+            Action::Skip => "Skip",
+            Action::SkipBlock => "Skip block",
+            Action::AddHalfMinute => "+30s",
+            Action::AddMinute => "+1m",
+            Action::RemoveHalfMinute => "-30s",
+            Action::Stop => "Stop",
+            Action::BlackAndWhite => "B/W",
+            Action::Mirror => "Mirror",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Data, Lens, Serialize, Deserialize)]
+pub struct KeyBinding {
+    pub action: Action,
+    /// The key in the notation described above, empty for no shortcut.
+    pub key: String,
+}
+
+impl KeyBinding {
+    fn new(action: Action, key: &str) -> Self {
+        KeyBinding {
+            action,
+            key: key.to_owned(),
+        }
+    }
+}
+
+pub fn default_bindings() -> Arc<Vec<KeyBinding>> {
+    Arc::new(vec![
+        KeyBinding::new(Action::PlayPause, "Space"),
+        KeyBinding::new(Action::Reload, "R"),
+        KeyBinding::new(Action::Pin, "P"),
+        KeyBinding::new(Action::Back, "ArrowLeft"),
+        KeyBinding::new(Action::Skip, "ArrowRight"),
+        KeyBinding::new(Action::SkipBlock, "Shift+ArrowRight"),
+        KeyBinding::new(Action::AddHalfMinute, "ArrowUp"),
+        KeyBinding::new(Action::AddMinute, "Shift+ArrowUp"),
+        KeyBinding::new(Action::RemoveHalfMinute, "ArrowDown"),
+        KeyBinding::new(Action::Stop, "Escape"),
+        KeyBinding::new(Action::BlackAndWhite, "B"),
+        KeyBinding::new(Action::Mirror, "M"),
+    ])
+}
+
+/// Names the key pressed in `event` in the binding notation.
+pub fn describe(event: &KeyEvent) -> String {
+    let mods = &event.mods;
+    let held = [mods.ctrl(), mods.alt(), mods.meta(), mods.shift()];
+    let mut name = String::new();
+
+    let character = matches!(event.key, KbKey::Character(_));
+    for (modifier, held) in MODIFIERS.iter().zip(held.iter()) {
+        if *held && !(character && *modifier == "Shift") {
+            name.push_str(modifier);
+            name.push('+');
+        }
+    }
+    match &event.key {
+        KbKey::Character(c) if c == " " => name.push_str("Space"),
+        KbKey::Character(c) => name.push_str(&c.to_uppercase()),
+        key => name.push_str(&key.to_string()),
+    }
+    name
+}
+
+/// Puts a binding into a form that compares equal for the same key, whatever
+/// the case or order of its modifiers.
+fn normalize(text: &str) -> String {
+    let text = text.trim();
+    let (modifiers, key) = match text.strip_suffix('+') {
+        // `+` itself as the key, as in `+` or `Ctrl++`.
+        Some(rest) if rest.is_empty() || rest.ends_with('+') => {
+            (rest.strip_suffix('+').unwrap_or(rest), "+")
+        }
+        _ => text.rsplit_once('+').unwrap_or(("", text)),
+    };
+    let character = key.chars().count() == 1;
+
+    let mut normalized = String::new();
+    for modifier in MODIFIERS.iter() {
+        let held = modifiers
+            .split('+')
+            .any(|held| held.trim().eq_ignore_ascii_case(modifier));
+        if held && !(character && *modifier == "Shift") {
+            normalized.push_str(modifier);
+            normalized.push('+');
+        }
+    }
+    normalized.push_str(key.trim());
+    normalized.to_lowercase()
+}
+
+/// The action bound to the key pressed in `event`, if any.
+pub fn find(bindings: &[KeyBinding], event: &KeyEvent) -> Option<Action> {
+    let pressed = normalize(&describe(event));
+    bindings
+        .iter()
+        .find(|binding| normalize(&binding.key) == pressed)
+        .map(|binding| binding.action)
+}
+
+/// Another action bound to the same key as `binding`.
+pub fn conflict(bindings: &[KeyBinding], binding: &KeyBinding) -> Option<Action> {
+    let key = normalize(&binding.key);
+    if key.is_empty() {
+        return None;
+    }
+    bindings
+        .iter()
+        .find(|other| other.action != binding.action && normalize(&other.key) == key)
+        .map(|other| other.action)
+}
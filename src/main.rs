@@ -2,16 +2,55 @@
 
 use druid::{AppLauncher, LocalizedString, PlatformError, WindowDesc};
 
+use std::env;
+use std::process;
+
+mod audio;
 mod controllers;
+mod curriculum;
 mod data;
 mod delegate;
+mod images;
+mod keys;
+mod notation;
+mod planner;
+mod presets;
+mod schedule;
+mod share;
 mod view;
+mod widgets;
 
 use data::ProgramData;
 use delegate::Delegate;
 use view::ui_builder;
 
+/// Returns the value of `--schedule <text>` or `--schedule=<text>`, if given.
+/// The schedule is used for this run only and isn't saved to the config.
+fn schedule_arg() -> Option<String> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--schedule" {
+            return args.next();
+        }
+        if let Some(text) = arg.strip_prefix("--schedule=") {
+            return Some(text.to_owned());
+        }
+    }
+    None
+}
+
 fn main() -> Result<(), PlatformError> {
+    let mut data = ProgramData::new();
+    if let Some(text) = schedule_arg() {
+        match notation::parse(&text) {
+            Ok(schedule) => data.config.override_schedule(schedule),
+            Err(error) => {
+                eprintln!("Invalid --schedule: {}", error.annotate(&text));
+                process::exit(2);
+            }
+        }
+    }
+
     let main_window = WindowDesc::new(ui_builder)
         .title(LocalizedString::new("Art practice").with_placeholder("Art practice"))
         .with_min_size((1280., 720.));
@@ -19,6 +58,6 @@ fn main() -> Result<(), PlatformError> {
     Ok(AppLauncher::with_window(main_window)
         .delegate(Delegate)
         .use_simple_logger()
-        .launch(ProgramData::new())
+        .launch(data)
         .expect("launch failed"))
 }
@@ -0,0 +1,451 @@
+//! Compact text notation for schedules, e.g. `10x30s, 5x1m, break 2m, 2x5m`.
+//!
+//! Entries are separated by commas or new lines and `#` starts a comment that
+//! runs to the end of the line. A block is `count x duration`, optionally
+//! preceded by `name:` and followed by a quoted instruction. Durations are
+//! written with `h`, `m` and `s` units and can be combined, as in `1m30s`.
+//! `break 2m` adds a rest after the block before it.
+
+use druid::text::format::{Formatter, Validation, ValidationError};
+use druid::text::Selection;
+
+use std::error::Error;
+use std::fmt;
+
+use crate::schedule::{Schedule, ScheduleBlock};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    /// Byte offset of the error in the parsed text.
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    fn new(src: &str, offset: usize, message: impl Into<String>) -> Self {
+        let before = &src[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+        ParseError {
+            offset,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            message: message.into(),
+        }
+    }
+
+    /// Formats the error together with the offending line and a marker under
+    /// the error position.
+    pub fn annotate(&self, src: &str) -> String {
+        let line = src.lines().nth(self.line - 1).unwrap_or("");
+        format!("{}\n  {}\n  {}^", self, line, " ".repeat(self.column - 1))
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+pub fn parse(src: &str) -> Result<Schedule, ParseError> {
+    Parser { src, pos: 0 }.parse()
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(mut self) -> Result<Schedule, ParseError> {
+        let mut blocks = vec![];
+
+        loop {
+            self.skip_blank();
+            if self.peek().is_none() {
+                break;
+            }
+            self.entry(&mut blocks)?;
+            self.skip_space();
+            match self.peek() {
+                None => break,
+                Some(',') | Some('\n') => {
+                    self.bump();
+                }
+                Some(c) => {
+                    return Err(self.error(
+                        self.pos,
+                        format!("unexpected '{}', expected ',' or a new line", c),
+                    ))
+                }
+            }
+        }
+
+        if blocks.is_empty() {
+            return Err(self.error(0, "expected at least one block such as 10x30s"));
+        }
+
+        Ok(Schedule::new(blocks))
+    }
+
+    fn entry(&mut self, blocks: &mut Vec<ScheduleBlock>) -> Result<(), ParseError> {
+        let start = self.pos;
+
+        if self.eat_keyword("break") {
+            self.skip_space();
+            if self.at_digit() {
+                let length = self.duration()?;
+                return match blocks.last_mut() {
+                    None => Err(self.error(start, "a break has to follow a block")),
+                    Some(block) if block.break_after.is_some() => {
+                        Err(self.error(start, "the block already has a break after it"))
+                    }
+                    Some(block) => {
+                        block.break_after = Some(length);
+                        Ok(())
+                    }
+                };
+            }
+            // Not a break after all, but a block name starting with "break".
+            self.pos = start;
+        }
+
+        let name = match self.peek() {
+            Some(c) if c.is_ascii_digit() => String::new(),
+            Some('"') => {
+                let name = self.quoted()?;
+                self.skip_space();
+                self.expect(':', "expected ':' after the block name")?;
+                name
+            }
+            _ => {
+                let rest = &self.src[self.pos..];
+                let end = rest.find([':', ',', '\n', '#']);
+                match end {
+                    Some(end) if rest[end..].starts_with(':') => {
+                        let name = rest[..end].trim().to_owned();
+                        self.pos += end + 1;
+                        name
+                    }
+                    _ => return Err(self.error(start, "expected a block such as 10x30s")),
+                }
+            }
+        };
+
+        self.skip_space();
+        let count_start = self.pos;
+        let count = self.number()?;
+        if count == 0 {
+            return Err(self.error(count_start, "a block needs at least one pose"));
+        }
+
+        self.skip_space();
+        match self.peek() {
+            Some('x') | Some('X') | Some('×') => {
+                self.bump();
+            }
+            _ => return Err(self.error(self.pos, "expected 'x' after the number of poses")),
+        }
+
+        self.skip_space();
+        let duration = self.duration()?;
+
+        self.skip_space();
+        let instruction = match self.peek() {
+            Some('"') => Some(self.quoted()?),
+            _ => None,
+        };
+
+        blocks.push(ScheduleBlock {
+            name,
+            instruction,
+            ..ScheduleBlock::new(count, duration)
+        });
+        Ok(())
+    }
+
+    fn duration(&mut self) -> Result<usize, ParseError> {
+        let start = self.pos;
+        let mut total: usize = 0;
+
+        loop {
+            let value = self.number()?;
+            let unit_start = self.pos;
+            let unit = self.word().to_lowercase();
+            let scale = match unit.as_str() {
+                "s" | "sec" | "secs" => 1,
+                "m" | "min" | "mins" => 60,
+                "h" | "hr" | "hrs" => 3600,
+                "" => return Err(self.error(unit_start, "expected a unit: s, m or h")),
+                _ => {
+                    return Err(self.error(
+                        unit_start,
+                        format!("unknown unit '{}', expected s, m or h", unit),
+                    ))
+                }
+            };
+            total = value
+                .checked_mul(scale)
+                .and_then(|value| total.checked_add(value))
+                .ok_or_else(|| self.error(start, "the duration is too long"))?;
+
+            if !self.at_digit() {
+                break;
+            }
+        }
+
+        if total == 0 {
+            return Err(self.error(start, "the duration has to be longer than 0s"));
+        }
+        Ok(total)
+    }
+
+    fn number(&mut self) -> Result<usize, ParseError> {
+        let start = self.pos;
+        let digits = self.take_while(|c| c.is_ascii_digit());
+
+        if digits.is_empty() {
+            return Err(self.error(start, "expected a number"));
+        }
+        digits
+            .parse()
+            .map_err(|_| self.error(start, "the number is too large"))
+    }
+
+    fn quoted(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        self.expect('"', "expected '\"'")?;
+
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(text),
+                Some('\\') => match self.bump() {
+                    Some(c @ '"') | Some(c @ '\\') => text.push(c),
+                    _ => return Err(self.error(self.pos, "expected '\"' or '\\' after '\\'")),
+                },
+                Some('\n') | None => return Err(self.error(start, "unterminated quote")),
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    fn word(&mut self) -> &'a str {
+        self.take_while(|c| c.is_alphabetic())
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let start = self.pos;
+        if self.word().eq_ignore_ascii_case(keyword) {
+            true
+        } else {
+            self.pos = start;
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char, message: &str) -> Result<(), ParseError> {
+        if self.peek() == Some(expected) {
+            self.bump();
+            Ok(())
+        } else {
+            Err(self.error(self.pos, message))
+        }
+    }
+
+    /// Skips spaces and comments, stopping at the end of the line.
+    fn skip_space(&mut self) {
+        loop {
+            match self.peek() {
+                Some('#') => {
+                    self.take_while(|c| c != '\n');
+                }
+                Some(c) if c.is_whitespace() && c != '\n' => {
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Skips spaces, comments and empty lines.
+    fn skip_blank(&mut self) {
+        loop {
+            self.skip_space();
+            if self.peek() == Some('\n') {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let rest = &self.src[self.pos..];
+        let end = rest.find(|c| !predicate(c)).unwrap_or(rest.len());
+        self.pos += end;
+        &rest[..end]
+    }
+
+    fn at_digit(&self) -> bool {
+        matches!(self.peek(), Some(c) if c.is_ascii_digit())
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn error(&self, offset: usize, message: impl Into<String>) -> ParseError {
+        ParseError::new(self.src, offset, message)
+    }
+}
+
+pub fn format(schedule: &Schedule) -> String {
+    let mut entries = vec![];
+
+    for block in schedule.blocks.iter() {
+        let mut entry = String::new();
+        if !block.name.is_empty() {
+            entry.push_str(&format_name(&block.name));
+            entry.push_str(": ");
+        }
+        entry.push_str(&format!(
+            "{}x{}",
+            block.count,
+            format_duration(block.duration)
+        ));
+        if let Some(instruction) = &block.instruction {
+            entry.push(' ');
+            entry.push_str(&quote(instruction));
+        }
+        entries.push(entry);
+
+        if let Some(length) = block.break_after {
+            entries.push(format!("break {}", format_duration(length)));
+        }
+    }
+
+    entries.join(", ")
+}
+
+pub fn format_duration(seconds: usize) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    let mut text = String::new();
+    if hours > 0 {
+        text.push_str(&format!("{}h", hours));
+    }
+    if minutes > 0 {
+        text.push_str(&format!("{}m", minutes));
+    }
+    if seconds > 0 || text.is_empty() {
+        text.push_str(&format!("{}s", seconds));
+    }
+    text
+}
+
+/// Formats a countdown as `m:ss`, rounding up to whole seconds.
+pub fn format_clock(seconds: f64) -> String {
+    let seconds = seconds.max(0.).ceil() as usize;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn format_name(name: &str) -> String {
+    let first_word = name
+        .split(|c: char| !c.is_alphabetic())
+        .next()
+        .unwrap_or("");
+    let plain = name.trim() == name
+        && !name.starts_with(|c: char| c.is_ascii_digit() || c == '"')
+        && !name.contains([':', ',', '\n', '#'])
+        && !first_word.eq_ignore_ascii_case("break");
+
+    if plain {
+        name.to_owned()
+    } else {
+        quote(name)
+    }
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Lets a `ValueTextBox` edit a schedule in the text notation.
+pub struct NotationFormatter;
+
+impl Formatter<Schedule> for NotationFormatter {
+    fn format(&self, value: &Schedule) -> String {
+        format(value)
+    }
+
+    fn validate_partial_input(&self, _input: &str, _sel: &Selection) -> Validation {
+        Validation::success()
+    }
+
+    fn value(&self, input: &str) -> Result<Schedule, ValidationError> {
+        parse(input).map_err(ValidationError::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_gives_back_parsed_text() {
+        for text in [
+            "10x30s, 5x1m, break 2m, 2x5m",
+            "Warm up: 10x30s \"loose lines\", break 1m30s, 1x1h",
+            "\"break dancing\": 3x2m, \"a, b\": 1x10s",
+            "say \"hi\": 2x45s \"back\\\\slash \\\"quoted\\\"\"",
+        ] {
+            let schedule = parse(text).unwrap();
+            assert_eq!(format(&schedule), text);
+            assert_eq!(*parse(&format(&schedule)).unwrap().blocks, *schedule.blocks);
+        }
+    }
+
+    #[test]
+    fn parse_accepts_loose_spacing_and_comments() {
+        let schedule = parse("  10 X 30s # gestures\n\n5×1m\nbreak 2m").unwrap();
+        assert_eq!(format(&schedule), "10x30s, 5x1m, break 2m");
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_column() {
+        let src = "10x30s, 5y1m";
+        let error = parse(src).unwrap_err();
+        assert_eq!((error.line, error.column), (1, 10));
+        assert_eq!(
+            error.annotate(src),
+            "line 1, column 10: expected 'x' after the number of poses\n  10x30s, 5y1m\n           ^"
+        );
+    }
+
+    #[test]
+    fn error_columns_count_characters_on_later_lines() {
+        let src = "10x30s\n5×30s, 2×1q";
+        let error = parse(src).unwrap_err();
+        assert_eq!((error.line, error.column), (2, 11));
+        assert_eq!(
+            error.annotate(src),
+            "line 2, column 11: unknown unit 'q', expected s, m or h\n  5×30s, 2×1q\n            ^"
+        );
+    }
+}
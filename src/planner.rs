@@ -0,0 +1,237 @@
+use druid::{Data, Lens};
+
+use serde::{Deserialize, Serialize};
+
+use crate::schedule::{Schedule, ScheduleBlock};
+
+/// How far off the target a planned session may end up, in seconds.
+pub const PLAN_TOLERANCE: usize = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq, Data, Serialize, Deserialize)]
+pub enum PlanStyle {
+    GestureHeavy,
+    Balanced,
+    LongPoseHeavy,
+}
+
+#[derive(Clone, Data, Lens, Serialize, Deserialize)]
+pub struct PlannerSettings {
+    pub target_minutes: usize,
+    pub style: PlanStyle,
+}
+
+impl Default for PlannerSettings {
+    fn default() -> Self {
+        PlannerSettings {
+            target_minutes: 45,
+            style: PlanStyle::Balanced,
+        }
+    }
+}
+
+/// Pose lengths a style is built from, shortest first, with the share of the
+/// session spent on each.
+fn template(style: PlanStyle) -> &'static [(usize, f64)] {
+    match style {
+        PlanStyle::GestureHeavy => &[(30, 0.4), (60, 0.3), (120, 0.2), (300, 0.1)],
+        PlanStyle::Balanced => &[(30, 0.15), (60, 0.2), (120, 0.2), (300, 0.25), (600, 0.2)],
+        PlanStyle::LongPoseHeavy => &[(60, 0.1), (300, 0.2), (900, 0.3), (1200, 0.4)],
+    }
+}
+
+fn block_name(duration: usize) -> &'static str {
+    match duration {
+        0..=30 => "Quick gestures",
+        31..=60 => "Gestures",
+        61..=120 => "Short poses",
+        121..=300 => "Medium poses",
+        _ => "Long poses",
+    }
+}
+
+/// Builds a schedule going from short to long poses that lasts `target`
+/// seconds, give or take `tolerance`. There is no plan for a zero target.
+pub fn plan(target: usize, style: PlanStyle, tolerance: usize) -> Option<Schedule> {
+    if target == 0 {
+        return None;
+    }
+    let template = template(style);
+
+    let mut counts: Vec<usize> = template
+        .iter()
+        .map(|&(duration, share)| (target as f64 * share / duration as f64) as usize)
+        .collect();
+
+    let total = |counts: &[usize]| -> usize {
+        counts
+            .iter()
+            .zip(template)
+            .map(|(count, (duration, _))| count * duration)
+            .sum()
+    };
+
+    // Fill what the shares left over, longest poses first.
+    for (id, &(duration, _)) in template.iter().enumerate().rev() {
+        let left = target - total(&counts);
+        counts[id] += left / duration;
+    }
+
+    let mut blocks: Vec<_> = counts
+        .iter()
+        .zip(template)
+        .filter(|(&count, _)| count > 0)
+        .map(|(&count, &(duration, _))| {
+            ScheduleBlock::new(count, duration).with_name(block_name(duration))
+        })
+        .collect();
+
+    match blocks.last_mut() {
+        // Shorter than the shortest pose of the style.
+        None => return Some(Schedule::new(vec![ScheduleBlock::new(1, target)])),
+        Some(last) => {
+            let left = target - total(&counts);
+            if left > tolerance {
+                last.duration += left / last.count;
+            }
+        }
+    }
+
+    Some(Schedule::new(blocks))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Data, Serialize, Deserialize)]
+pub enum RampKind {
+    /// Pose lengths grow by the same number of seconds each block.
+    Linear,
+    /// Pose lengths grow by the same factor each block.
+    Geometric,
+    /// Pose lengths follow the usual class steps (30s, 1m, 2m, 5m, ...) and
+    /// longer blocks get fewer poses.
+    Classroom,
+}
+
+#[derive(Clone, Data, Lens, Serialize, Deserialize)]
+pub struct RampSettings {
+    /// Pose length of the first block in seconds.
+    pub start: usize,
+    /// Pose length of the last block in seconds.
+    pub end: usize,
+    pub blocks: usize,
+    /// Poses in each block, or in the first block of a classroom ramp.
+    pub poses_per_block: usize,
+}
+
+impl Default for RampSettings {
+    fn default() -> Self {
+        RampSettings {
+            start: 30,
+            end: 300,
+            blocks: 4,
+            poses_per_block: 3,
+        }
+    }
+}
+
+const CLASSROOM_STEPS: [usize; 9] = [30, 60, 120, 300, 600, 900, 1200, 1800, 3600];
+
+/// Rounds a pose length to whole 5 seconds.
+fn round_duration(seconds: f64) -> usize {
+    ((seconds / 5.).round() as usize * 5).max(5)
+}
+
+/// Builds a warm-up schedule whose pose lengths ramp from `start` to `end`.
+pub fn ramp(kind: RampKind, settings: &RampSettings) -> Schedule {
+    let blocks = settings.blocks.max(1);
+    let poses = settings.poses_per_block.max(1);
+    let (start, end) = (settings.start.max(1) as f64, settings.end.max(1) as f64);
+    // Position of each block along the ramp, from 0 to 1.
+    let steps = (0..blocks).map(|id| match blocks {
+        1 => 0.,
+        _ => id as f64 / (blocks - 1) as f64,
+    });
+
+    let durations: Vec<usize> = match kind {
+        RampKind::Linear => steps
+            .map(|t| round_duration(start + (end - start) * t))
+            .collect(),
+        RampKind::Geometric => steps
+            .map(|t| round_duration(start * (end / start).powf(t)))
+            .collect(),
+        RampKind::Classroom => {
+            let (low, high) = (start.min(end) as usize, start.max(end) as usize);
+            let mut ladder: Vec<_> = CLASSROOM_STEPS
+                .iter()
+                .copied()
+                .filter(|&step| low <= step && step <= high)
+                .collect();
+            if ladder.is_empty() {
+                ladder.push(round_duration(start));
+            }
+            if start > end {
+                ladder.reverse();
+            }
+            steps
+                .map(|t| ladder[(t * (ladder.len() - 1) as f64).round() as usize])
+                .collect()
+        }
+    };
+
+    let first = durations[0];
+    Schedule::new(
+        durations
+            .into_iter()
+            .map(|duration| {
+                let count = match kind {
+                    RampKind::Classroom => {
+                        ((poses * first) as f64 / duration as f64).round().max(1.) as usize
+                    }
+                    _ => poses,
+                };
+                ScheduleBlock::new(count, duration).with_name(block_name(duration))
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STYLES: [PlanStyle; 3] = [
+        PlanStyle::GestureHeavy,
+        PlanStyle::Balanced,
+        PlanStyle::LongPoseHeavy,
+    ];
+
+    #[test]
+    fn plans_last_the_target_within_tolerance() {
+        for &style in STYLES.iter() {
+            for minutes in 1..=300 {
+                let target = minutes * 60;
+                let schedule = plan(target, style, PLAN_TOLERANCE).unwrap();
+                let total = schedule.total_duration();
+                assert!(
+                    total.max(target) - total.min(target) <= PLAN_TOLERANCE,
+                    "{:?} plan for {}m lasts {}s",
+                    style,
+                    minutes,
+                    total
+                );
+                assert!(schedule.validate().is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn short_targets_get_a_single_pose() {
+        let schedule = plan(20, PlanStyle::LongPoseHeavy, PLAN_TOLERANCE).unwrap();
+        assert_eq!(*schedule.blocks, vec![ScheduleBlock::new(1, 20)]);
+    }
+
+    #[test]
+    fn zero_target_has_no_plan() {
+        for &style in STYLES.iter() {
+            assert!(plan(0, style, PLAN_TOLERANCE).is_none());
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use druid::{Data, Lens};
+
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::sync::Arc;
+
+use crate::data::{get_presets_path, load_ron, save_ron};
+use crate::schedule::{Schedule, ScheduleBlock};
+
+#[derive(Clone, Data, Lens, Serialize, Deserialize)]
+pub struct Preset {
+    /// Identifies the preset for the duration of the run, assigned on load.
+    #[serde(skip)]
+    pub id: usize,
+    pub name: String,
+    pub schedule: Schedule,
+    /// Built-in presets ship with the app and are never written to disk.
+    #[serde(skip)]
+    pub builtin: bool,
+}
+
+fn builtin_presets() -> Vec<Preset> {
+    let builtin = |name: &str, blocks| Preset {
+        id: 0,
+        name: name.to_owned(),
+        schedule: Schedule::new(blocks),
+        builtin: true,
+    };
+
+    vec![
+        builtin(
+            "30s gestures",
+            vec![ScheduleBlock::new(20, 30).with_name("Gestures")],
+        ),
+        builtin(
+            "Class mode",
+            vec![
+                ScheduleBlock::new(10, 30).with_name("Warm up"),
+                ScheduleBlock::new(5, 60).with_name("Gestures"),
+                ScheduleBlock::new(3, 120)
+                    .with_name("Short poses")
+                    .with_break(120),
+                ScheduleBlock::new(2, 300).with_name("Medium poses"),
+                ScheduleBlock::new(1, 600).with_name("Long pose"),
+            ],
+        ),
+        builtin(
+            "Long pose",
+            vec![
+                ScheduleBlock::new(4, 60).with_name("Warm up"),
+                ScheduleBlock::new(1, 1200).with_name("Long pose"),
+            ],
+        ),
+    ]
+}
+
+#[derive(Clone, Data, Lens)]
+pub struct Presets {
+    pub list: Arc<Vec<Preset>>,
+}
+
+impl Presets {
+    pub fn new() -> Self {
+        let user_presets: Vec<Preset> = load_ron(get_presets_path()).unwrap_or_default();
+
+        let list = builtin_presets()
+            .into_iter()
+            .chain(user_presets)
+            .enumerate()
+            .map(|(id, preset)| Preset { id, ..preset })
+            .collect();
+
+        Presets {
+            list: Arc::new(list),
+        }
+    }
+
+    pub fn try_save(&self) -> io::Result<()> {
+        let user_presets: Vec<_> = self.list.iter().filter(|p| !p.builtin).collect();
+        save_ron(get_presets_path(), &user_presets)
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Preset> {
+        self.list.iter().find(|p| p.id == id)
+    }
+
+    /// Adds a user preset, making the name unique if needed.
+    pub fn add(&mut self, name: &str, schedule: Schedule) {
+        let preset = Preset {
+            id: self.list.iter().map(|p| p.id + 1).max().unwrap_or(0),
+            name: self.unique_name(name),
+            schedule,
+            builtin: false,
+        };
+
+        let mut list = (*self.list).clone();
+        list.push(preset);
+        self.list = Arc::new(list);
+    }
+
+    pub fn duplicate(&mut self, id: usize) {
+        if let Some(preset) = self.get(id).cloned() {
+            self.add(&format!("{} copy", preset.name), preset.schedule);
+        }
+    }
+
+    pub fn delete(&mut self, id: usize) {
+        let list = self
+            .list
+            .iter()
+            .filter(|p| p.builtin || p.id != id)
+            .cloned()
+            .collect();
+        self.list = Arc::new(list);
+    }
+
+    fn unique_name(&self, name: &str) -> String {
+        let taken = |name: &str| self.list.iter().any(|p| p.name == name);
+
+        if !taken(name) {
+            return name.to_owned();
+        }
+        (2..)
+            .map(|n| format!("{} {}", name, n))
+            .find(|name| !taken(name))
+            .unwrap()
+    }
+}
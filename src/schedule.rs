@@ -0,0 +1,232 @@
+use druid::{Data, Lens};
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+#[derive(Clone, Debug, Default, PartialEq, Data, Lens, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BlockOptions {
+    /// Pause the player when the session enters this block.
+    pub pause_before: bool,
+    /// Number of following poses that keep an image picked in this block,
+    /// so the same reference can be drawn again at other lengths.
+    pub hold_image: usize,
+    /// Keep an image picked in this block until the block ends.
+    pub repeat_image: bool,
+    /// Tag or subfolder the block takes its images from instead of the whole
+    /// images directory.
+    pub pool: Option<String>,
+    /// Show the block's images in black and white unless toggled off.
+    pub black_and_white: bool,
+    /// Show the block's images mirrored unless toggled off.
+    pub mirrored: bool,
+    /// Prompts one of which is picked at random for each pose.
+    pub prompts: Arc<Vec<String>>,
+    /// Seconds between metronome ticks during poses, 0 for no metronome.
+    pub metronome: usize,
+    /// Final seconds of a pose that get an accented tick every second.
+    pub accent_seconds: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Data, Lens, Serialize, Deserialize)]
+pub struct ScheduleBlock {
+    #[serde(default)]
+    pub name: String,
+    /// Number of poses in the block.
+    pub count: usize,
+    /// Length of a single pose in seconds.
+    pub duration: usize,
+    /// Length of the rest after the block in seconds.
+    #[serde(default)]
+    pub break_after: Option<usize>,
+    #[serde(default)]
+    pub instruction: Option<String>,
+    #[serde(default)]
+    pub options: BlockOptions,
+}
+
+impl ScheduleBlock {
+    pub fn new(count: usize, duration: usize) -> Self {
+        ScheduleBlock {
+            name: String::new(),
+            count,
+            duration,
+            break_after: None,
+            instruction: None,
+            options: BlockOptions::default(),
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_break(mut self, length: usize) -> Self {
+        self.break_after = Some(length);
+        self
+    }
+
+    /// Length of the block in seconds, including the break after it.
+    pub fn total_duration(&self) -> usize {
+        self.count * self.duration + self.break_after.unwrap_or(0)
+    }
+
+    pub fn problem(&self) -> Option<BlockProblem> {
+        if self.count == 0 {
+            Some(BlockProblem::NoPoses)
+        } else if self.duration == 0 {
+            Some(BlockProblem::NoDuration)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Debug, Data, Lens, Serialize, Deserialize)]
+#[serde(from = "ScheduleRepr")]
+pub struct Schedule {
+    pub blocks: Arc<Vec<ScheduleBlock>>,
+}
+
+/// Formats `Schedule` can be read from. Configs saved before blocks had names
+/// stored the schedule as a bare list of `(count, seconds)` pairs.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScheduleRepr {
+    Blocks { blocks: Vec<ScheduleBlock> },
+    Pairs(Vec<(usize, usize)>),
+}
+
+impl From<ScheduleRepr> for Schedule {
+    fn from(repr: ScheduleRepr) -> Self {
+        match repr {
+            ScheduleRepr::Blocks { blocks } => Schedule::new(blocks),
+            ScheduleRepr::Pairs(pairs) => Schedule::new(
+                pairs
+                    .into_iter()
+                    .map(|(count, duration)| ScheduleBlock::new(count, duration))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule::new(vec![ScheduleBlock::new(5, 30), ScheduleBlock::new(5, 60)])
+    }
+}
+
+impl Schedule {
+    pub fn new(blocks: Vec<ScheduleBlock>) -> Self {
+        Schedule {
+            blocks: Arc::new(blocks),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn block(&self, id: usize) -> &ScheduleBlock {
+        &self.blocks[id]
+    }
+
+    /// Length of the whole session in seconds, breaks included.
+    pub fn total_duration(&self) -> usize {
+        self.blocks.iter().map(ScheduleBlock::total_duration).sum()
+    }
+
+    /// Carries over per-block options from `old` to the blocks at the same
+    /// positions, for edits that only describe the timing of each block.
+    pub fn keep_options_from(mut self, old: &Schedule) -> Self {
+        let blocks = self
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(id, block)| {
+                let mut block = block.clone();
+                if let Some(old) = old.blocks.get(id) {
+                    block.options = old.options.clone();
+                }
+                block
+            })
+            .collect();
+        self.blocks = Arc::new(blocks);
+        self
+    }
+
+    /// Checks that the schedule can be played. Zero length breaks are dropped
+    /// rather than rejected.
+    pub fn validate(&self) -> Result<ValidSchedule, ScheduleError> {
+        if self.is_empty() {
+            return Err(ScheduleError::Empty);
+        }
+
+        let blocks = self
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(id, block)| {
+                if let Some(problem) = block.problem() {
+                    return Err(ScheduleError::Block { block: id, problem });
+                }
+                let mut block = block.clone();
+                block.break_after = block.break_after.filter(|&length| length > 0);
+                Ok(block)
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(ValidSchedule(Schedule::new(blocks)))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlockProblem {
+    NoPoses,
+    NoDuration,
+}
+
+impl fmt::Display for BlockProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockProblem::NoPoses => write!(f, "needs at least one pose"),
+            BlockProblem::NoDuration => write!(f, "needs a pose length above 0s"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScheduleError {
+    Empty,
+    Block { block: usize, problem: BlockProblem },
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleError::Empty => write!(f, "the schedule has no blocks"),
+            ScheduleError::Block { block, problem } => write!(f, "block {} {}", block + 1, problem),
+        }
+    }
+}
+
+/// A schedule that has been checked to have at least one block and no empty
+/// blocks, so stepping through it can't go out of bounds.
+#[derive(Clone, Data)]
+pub struct ValidSchedule(Schedule);
+
+impl Deref for ValidSchedule {
+    type Target = Schedule;
+
+    fn deref(&self) -> &Schedule {
+        &self.0
+    }
+}
@@ -0,0 +1,118 @@
+//! Share codes and files for passing schedules around.
+//!
+//! A share code is a version prefix followed by the schedule in RON,
+//! deflated and base64url encoded, so it survives being pasted in chats and
+//! URLs. Files hold the plain schedule as RON, or JSON when they end in
+//! `.json`.
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+use ron::ser::{to_string_pretty, PrettyConfig};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::schedule::Schedule;
+
+/// Prefix of share codes made by this version, bumped if the encoding
+/// changes.
+const CODE_PREFIX: &str = "ap1.";
+
+/// Most bytes a share code may inflate to, far above any real schedule, so
+/// a crafted code can't use up memory.
+const DECODED_LIMIT: u64 = 256 * 1024;
+
+#[derive(Debug)]
+pub enum ShareError {
+    /// The code was made by a newer version or isn't a share code at all.
+    UnknownVersion,
+    Corrupted,
+    Io(io::Error),
+    Format(String),
+}
+
+impl fmt::Display for ShareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShareError::UnknownVersion => write!(f, "not a share code this version can read"),
+            ShareError::Corrupted => write!(f, "the share code is damaged or incomplete"),
+            ShareError::Io(error) => write!(f, "{}", error),
+            ShareError::Format(error) => write!(f, "not a valid schedule: {}", error),
+        }
+    }
+}
+
+impl Error for ShareError {}
+
+impl From<io::Error> for ShareError {
+    fn from(error: io::Error) -> Self {
+        ShareError::Io(error)
+    }
+}
+
+pub fn encode(schedule: &Schedule) -> String {
+    let text = ron::to_string(schedule).expect("Unable to serialize schedule");
+
+    let mut encoder = DeflateEncoder::new(vec![], Compression::best());
+    encoder
+        .write_all(text.as_bytes())
+        .and_then(|_| encoder.finish())
+        .map(|bytes| {
+            format!(
+                "{}{}",
+                CODE_PREFIX,
+                base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+            )
+        })
+        .expect("Unable to compress schedule")
+}
+
+pub fn decode(code: &str) -> Result<Schedule, ShareError> {
+    let payload = code
+        .trim()
+        .strip_prefix(CODE_PREFIX)
+        .ok_or(ShareError::UnknownVersion)?;
+    let bytes = base64::decode_config(payload, base64::URL_SAFE_NO_PAD)
+        .map_err(|_| ShareError::Corrupted)?;
+
+    let mut text = String::new();
+    DeflateDecoder::new(bytes.as_slice())
+        .take(DECODED_LIMIT + 1)
+        .read_to_string(&mut text)
+        .map_err(|_| ShareError::Corrupted)?;
+    if text.len() as u64 > DECODED_LIMIT {
+        return Err(ShareError::Corrupted);
+    }
+
+    ron::from_str(&text).map_err(|_| ShareError::Corrupted)
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+pub fn export_file(path: &Path, schedule: &Schedule) -> Result<(), ShareError> {
+    let text = if is_json(path) {
+        serde_json::to_string_pretty(schedule).map_err(|e| ShareError::Format(e.to_string()))?
+    } else {
+        to_string_pretty(schedule, PrettyConfig::new().depth_limit(3))
+            .map_err(|e| ShareError::Format(e.to_string()))?
+    };
+    fs::write(path, text)?;
+    Ok(())
+}
+
+pub fn import_file(path: &Path) -> Result<Schedule, ShareError> {
+    let text = fs::read_to_string(path)?;
+
+    if is_json(path) {
+        serde_json::from_str(&text).map_err(|e| ShareError::Format(e.to_string()))
+    } else {
+        ron::from_str(&text).map_err(|e| ShareError::Format(e.to_string()))
+    }
+}
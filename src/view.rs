@@ -1,17 +1,38 @@
 use druid::{
-    lens,
-    widget::{Button, FillStrat, Flex, Image, Label, List, Tabs, TextBox},
-    Command, Env, FileDialogOptions, ImageBuf, LensExt, Target, Widget, WidgetExt,
+    lens, theme,
+    widget::{
+        Button, Checkbox, CrossAxisAlignment, Either, FillStrat, Flex, Image, Label, List,
+        ProgressBar, Radio, Scroll, Slider, Tabs, TextBox, ValueTextBox,
+    },
+    Color, Command, Env, FileDialogOptions, FileSpec, ImageBuf, LensExt, Target, Widget, WidgetExt,
 };
 
-use std::{path::PathBuf, sync::Arc};
+use chrono::Local;
 
-use crate::data::{AutoStepState, Config, ProgramData, START_AUTO_STEP, STOP_AUTO_STEP};
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use crate::data::{
+    AutoStepData, AutoStepState, Config, EndPolicy, LeadIn, ProgramData, Step, TimerDisplay,
+    START_AUTO_STEP, STOP_AUTO_STEP,
+};
 use crate::{
-    controllers::{AutoStepControl, UpdateImage},
-    data::{TOGGLE_BW, TOGGLE_MIRROR},
+    audio::{CueSettings, SoundSettings},
+    controllers::{AutoStepControl, KeyboardControl, NotationErrors, Refresh, UpdateImage},
+    data::{
+        COPY_SHARE_CODE, DELETE_PRESET, DUPLICATE_PRESET, IMPORT_SHARE_CODE, LOAD_PRESET,
+        SAVE_PRESET, TOGGLE_BW, TOGGLE_MIRROR,
+    },
+    images::DurationPolicy,
+    keys::{conflict, KeyBinding},
+    notation::{format_clock, format_duration, NotationFormatter},
+    planner::{plan, ramp, PlanStyle, PlannerSettings, RampKind, RampSettings, PLAN_TOLERANCE},
+    presets::{Preset, Presets},
+    schedule::{BlockOptions, Schedule, ScheduleBlock, ScheduleError},
+    widgets::{LeadInCover, TimeOverlay, Timeline, UpcomingStrip, WARNING_COLOR},
 };
 
+const ERROR_COLOR: Color = Color::rgb8(0xe0, 0x40, 0x40);
+
 pub fn ui_builder() -> impl Widget<ProgramData> {
     let presentation = presentation_ui_builder();
     let configuration = configuration_ui_builder();
@@ -32,6 +53,16 @@ pub fn configuration_ui_builder() -> impl Widget<ProgramData> {
         |data: &Arc<Option<PathBuf>>, _: &Env| format! {"Current directory: {}", Option::as_ref(&data).map(|x| x.to_str().unwrap()).unwrap_or("None")},
     ).lens(ProgramData::config.then(Config::current_directory));
 
+    let subfolders = Checkbox::new("Include subfolders").lens(lens::Identity.map(
+        |x: &ProgramData| x.config.scan_subfolders,
+        |x: &mut ProgramData, y: bool| {
+            if x.config.scan_subfolders != y {
+                x.config.scan_subfolders = y;
+                x.prepare_images(true);
+            }
+        },
+    ));
+
     let open = Button::new("Change").on_click(move |ctx, _, _| {
         ctx.submit_command(Command::new(
             druid::commands::SHOW_OPEN_PANEL,
@@ -40,49 +71,516 @@ pub fn configuration_ui_builder() -> impl Widget<ProgramData> {
         ))
     });
 
+    let notation = ValueTextBox::new(TextBox::new(), NotationFormatter)
+        .delegate(NotationErrors)
+        .lens(ProgramData::config.then(Config::schedule.map(
+            |x: &Schedule| x.clone(),
+            |x: &mut Schedule, y: Schedule| {
+                // The text box writes back on every focus change, so only an
+                // actual edit replaces the schedule.
+                let y = y.keep_options_from(x);
+                if *x.blocks != *y.blocks {
+                    *x = y;
+                }
+            },
+        )));
+
+    let notation_error = Label::new(|data: &ProgramData, _env: &Env| {
+        data.notation_error.clone().unwrap_or_default()
+    })
+    .with_text_color(ERROR_COLOR);
+
     let schedule_ui = schedule_ui_builder().lens(ProgramData::config.then(Config::schedule));
 
-    Flex::column()
-        .with_child(Flex::row().with_child(current_dir_label).with_child(open))
+    let planner_ui = planner_ui_builder();
+
+    let ramp_ui = ramp_ui_builder();
+
+    let presets_ui = presets_ui_builder().lens(ProgramData::presets);
+
+    let share_ui = share_ui_builder();
+
+    let sounds_ui = sounds_ui_builder().lens(ProgramData::config.then(Config::sounds));
+
+    let keys_ui = keys_ui_builder().lens(ProgramData::config.then(Config::key_bindings));
+
+    let settings = Flex::column()
+        .with_child(
+            Flex::row()
+                .with_child(current_dir_label)
+                .with_child(open)
+                .with_child(subfolders),
+        )
+        .with_child(
+            Flex::row()
+                .with_child(Label::new("Schedule:"))
+                .with_flex_child(notation.expand_width(), 1.0),
+        )
+        .with_child(notation_error)
         .with_child(schedule_ui)
+        .with_child(planner_ui)
+        .with_child(ramp_ui)
+        .with_child(
+            Flex::row()
+                .with_child(Label::new("When the schedule ends:"))
+                .with_child(Radio::new("Stop", EndPolicy::Stop))
+                .with_child(Radio::new("Loop", EndPolicy::Loop))
+                .with_child(Radio::new("Repeat last block", EndPolicy::RepeatLastBlock))
+                .with_child(Radio::new("Pause", EndPolicy::Pause))
+                .lens(ProgramData::config.then(Config::end_policy)),
+        )
+        .with_child(
+            Flex::row()
+                .with_child(Label::new(
+                    "Pose lengths from <image>.ron and playlist.ron:",
+                ))
+                .with_child(Radio::new("Replace with duration", DurationPolicy::Replace))
+                .with_child(Radio::new("Multiply by scale", DurationPolicy::Scale))
+                .with_child(Radio::new("Cap at duration", DurationPolicy::Cap))
+                .lens(ProgramData::config.then(Config::duration_policy)),
+        )
+        .with_child(
+            Flex::row()
+                .with_child(Label::new("Get ready for"))
+                .with_child(number_box().lens(LeadIn::every_pose))
+                .with_child(Label::new("s before each pose and"))
+                .with_child(number_box().lens(LeadIn::session_start))
+                .with_child(Label::new("s before the session"))
+                .with_child(Checkbox::new("Hide image").lens(LeadIn::hide_image))
+                .lens(ProgramData::config.then(Config::lead_in)),
+        )
+        .with_child(
+            Flex::row()
+                .with_child(Checkbox::new("Progress bar").lens(TimerDisplay::progress_bar))
+                .with_child(Label::new("Colour the timer"))
+                .with_child(number_box().lens(TimerDisplay::warning_seconds))
+                .with_child(Label::new("s before a pose ends"))
+                .with_child(Checkbox::new("Blink").lens(TimerDisplay::flash))
+                .with_child(Checkbox::new("Large timer over the image").lens(TimerDisplay::overlay))
+                .lens(ProgramData::config.then(Config::timer)),
+        )
+        .with_child(
+            Flex::row()
+                .with_child(Label::new("Preview the next"))
+                .with_child(number_box().lens(ProgramData::config.then(Config::preview)))
+                .with_child(Label::new("images")),
+        )
+        .with_child(
+            Flex::row()
+                .with_child(Label::new("Break message:"))
+                .with_child(
+                    TextBox::new()
+                        .with_placeholder("Blank screen")
+                        .lens(ProgramData::config.then(Config::break_message)),
+                ),
+        )
+        .with_child(sounds_ui)
+        .with_child(keys_ui)
+        .with_child(share_ui)
+        .with_child(presets_ui);
+
+    Scroll::new(settings).vertical()
+}
+
+fn cue_ui_builder(name: &str) -> impl Widget<CueSettings> {
+    Flex::row()
+        .with_child(Checkbox::new(name).lens(CueSettings::enabled))
+        .with_child(Slider::new().lens(CueSettings::volume))
+        .with_child(
+            TextBox::new()
+                .with_placeholder("Built-in sound")
+                .lens(CueSettings::file.map(
+                    |x: &Option<Arc<PathBuf>>| {
+                        x.as_ref()
+                            .map_or(String::new(), |x| x.display().to_string())
+                    },
+                    |x: &mut Option<Arc<PathBuf>>, y: String| {
+                        *x = if y.is_empty() {
+                            None
+                        } else {
+                            Some(Arc::new(PathBuf::from(y)))
+                        };
+                    },
+                ))
+                .fix_width(250.),
+        )
 }
 
-pub fn schedule_ui_builder() -> impl Widget<Arc<Vec<(usize, usize)>>> {
+pub fn sounds_ui_builder() -> impl Widget<SoundSettings> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new("Sounds (volume, WAV or OGG file):"))
+        .with_child(cue_ui_builder("Pose change").lens(SoundSettings::pose_change))
+        .with_child(
+            Flex::row()
+                .with_child(cue_ui_builder("Pose ending").lens(SoundSettings::seconds_left))
+                .with_child(number_box().lens(SoundSettings::warning_seconds))
+                .with_child(Label::new("s before the end, apart from the timer colour")),
+        )
+        .with_child(cue_ui_builder("Block change").lens(SoundSettings::block_change))
+        .with_child(cue_ui_builder("Session end").lens(SoundSettings::session_end))
+        .with_child(cue_ui_builder("Metronome").lens(SoundSettings::metronome))
+}
+
+pub fn keys_ui_builder() -> impl Widget<Arc<Vec<KeyBinding>>> {
+    let bindings = List::new(|| {
+        Flex::row()
+            .with_child(
+                Label::new(
+                    |(_, binding): &(Arc<Vec<KeyBinding>>, KeyBinding), _env: &Env| {
+                        binding.action.to_string()
+                    },
+                )
+                .fix_width(100.),
+            )
+            .with_child(
+                TextBox::new()
+                    .with_placeholder("None")
+                    .lens(lens!((Arc<Vec<KeyBinding>>, KeyBinding), 1).then(KeyBinding::key))
+                    .fix_width(150.),
+            )
+            .with_child(
+                Label::new(
+                    |(bindings, binding): &(Arc<Vec<KeyBinding>>, KeyBinding), _env: &Env| {
+                        conflict(bindings, binding)
+                            .map(|other| format!("Also bound to {}", other))
+                            .unwrap_or_default()
+                    },
+                )
+                .with_text_color(ERROR_COLOR),
+            )
+    })
+    .lens(lens::Identity.map(
+        |x: &Arc<Vec<KeyBinding>>| (x.clone(), x.clone()),
+        |x: &mut Arc<Vec<KeyBinding>>, (_, y): (Arc<Vec<KeyBinding>>, Arc<Vec<KeyBinding>>)| *x = y,
+    ));
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new("Keyboard shortcuts on the Player tab:"))
+        .with_child(bindings)
+}
+
+pub fn share_ui_builder() -> impl Widget<ProgramData> {
+    let schedule_files = vec![
+        FileSpec::new("RON schedule", &["ron"]),
+        FileSpec::new("JSON schedule", &["json"]),
+    ];
+    let import_options = FileDialogOptions::new()
+        .allowed_types(schedule_files.clone())
+        .title("Import schedule")
+        .button_text("Import");
+    let export_options = FileDialogOptions::new()
+        .allowed_types(schedule_files)
+        .default_name("schedule.ron")
+        .title("Export schedule")
+        .button_text("Export");
+
+    Flex::column()
+        .with_child(
+            Flex::row()
+                .with_child(Button::new("Copy code").on_click(|ctx, _, _| {
+                    ctx.submit_command(COPY_SHARE_CODE);
+                }))
+                .with_child(Button::new("Import code").on_click(|ctx, _, _| {
+                    ctx.submit_command(IMPORT_SHARE_CODE);
+                }))
+                .with_child(Button::new("Import file").on_click(move |ctx, _, _| {
+                    ctx.submit_command(Command::new(
+                        druid::commands::SHOW_OPEN_PANEL,
+                        import_options.clone(),
+                        Target::Auto,
+                    ))
+                }))
+                .with_child(Button::new("Export file").on_click(move |ctx, _, _| {
+                    ctx.submit_command(Command::new(
+                        druid::commands::SHOW_SAVE_PANEL,
+                        export_options.clone(),
+                        Target::Auto,
+                    ))
+                })),
+        )
+        .with_child(Label::new(|data: &ProgramData, _env: &Env| {
+            data.share_status.clone().unwrap_or_default()
+        }))
+}
+
+pub fn planner_ui_builder() -> impl Widget<ProgramData> {
+    let settings = Flex::row()
+        .with_child(Label::new("Plan a session of"))
+        .with_child(TextBox::new().lens(PlannerSettings::target_minutes.map(
+            |x: &usize| x.to_string(),
+            |x: &mut usize, y: String| {
+                *x = y.parse::<usize>().ok().filter(|&y| y > 0).unwrap_or(*x)
+            },
+        )))
+        .with_child(Label::new("min"))
+        .with_child(
+            Radio::new("Gesture heavy", PlanStyle::GestureHeavy).lens(PlannerSettings::style),
+        )
+        .with_child(Radio::new("Balanced", PlanStyle::Balanced).lens(PlannerSettings::style))
+        .with_child(
+            Radio::new("Long pose heavy", PlanStyle::LongPoseHeavy).lens(PlannerSettings::style),
+        )
+        .lens(ProgramData::config.then(Config::planner));
+
+    Flex::row().with_child(settings).with_child(
+        Button::new("Generate")
+            .on_click(|_ctx, data: &mut ProgramData, _| {
+                let settings = &data.config.planner;
+                if let Some(schedule) =
+                    plan(settings.target_minutes * 60, settings.style, PLAN_TOLERANCE)
+                {
+                    data.config.schedule = schedule;
+                }
+            })
+            .padding(5.),
+    )
+}
+
+/// A text box for a number, ignoring input that doesn't parse.
+fn number_box() -> impl Widget<usize> {
+    TextBox::new().lens(lens::Identity.map(
+        |x: &usize| x.to_string(),
+        |x: &mut usize, y: String| *x = y.parse::<usize>().unwrap_or(*x),
+    ))
+}
+
+pub fn ramp_ui_builder() -> impl Widget<ProgramData> {
+    let settings = Flex::row()
+        .with_child(Label::new("Ramp from"))
+        .with_child(number_box().lens(RampSettings::start))
+        .with_child(Label::new("s to"))
+        .with_child(number_box().lens(RampSettings::end))
+        .with_child(Label::new("s,"))
+        .with_child(number_box().lens(RampSettings::blocks))
+        .with_child(Label::new("blocks of"))
+        .with_child(number_box().lens(RampSettings::poses_per_block))
+        .with_child(Label::new("poses"))
+        .lens(ProgramData::config.then(Config::ramp));
+
+    let generate = |label, kind| {
+        Button::new(label)
+            .on_click(move |_ctx, data: &mut ProgramData, _| {
+                data.config.schedule = ramp(kind, &data.config.ramp);
+            })
+            .padding(5.)
+    };
+
+    Flex::row()
+        .with_child(settings)
+        .with_child(generate("Linear", RampKind::Linear))
+        .with_child(generate("Geometric", RampKind::Geometric))
+        .with_child(generate("Classroom", RampKind::Classroom))
+}
+
+pub fn presets_ui_builder() -> impl Widget<Presets> {
+    Flex::column()
+        .with_child(
+            Flex::row().with_child(Label::new("Presets")).with_child(
+                Button::new("Save current schedule")
+                    .on_click(|ctx, _data: &mut Presets, _| {
+                        ctx.submit_command(SAVE_PRESET);
+                    })
+                    .padding(5.),
+            ),
+        )
+        .with_child(
+            List::new(|| {
+                Flex::row()
+                    .with_child(Either::new(
+                        |data: &Preset, _env: &Env| data.builtin,
+                        Label::new(|data: &Preset, _env: &Env| format!("{} (built-in)", data.name)),
+                        TextBox::new().lens(Preset::name),
+                    ))
+                    .with_child(Button::new("Load").on_click(|ctx, data: &mut Preset, _| {
+                        ctx.submit_command(LOAD_PRESET.with(data.id));
+                    }))
+                    .with_child(
+                        Button::new("Duplicate").on_click(|ctx, data: &mut Preset, _| {
+                            ctx.submit_command(DUPLICATE_PRESET.with(data.id));
+                        }),
+                    )
+                    .with_child(Either::new(
+                        |data: &Preset, _env: &Env| data.builtin,
+                        Label::new(""),
+                        Button::new("Delete").on_click(|ctx, data: &mut Preset, _| {
+                            ctx.submit_command(DELETE_PRESET.with(data.id));
+                        }),
+                    ))
+            })
+            .lens(Presets::list),
+        )
+}
+
+pub fn schedule_ui_builder() -> impl Widget<Schedule> {
+    let total = Label::new(|data: &Schedule, _env: &Env| {
+        let total = data.total_duration();
+        let end = Local::now() + chrono::Duration::seconds(total as i64);
+        format!(
+            "Total: {}, ends at {} if started now",
+            format_duration(total),
+            end.format("%H:%M")
+        )
+    })
+    .controller(Refresh::every(Duration::from_secs(10)));
+
+    let schedule_error = Label::new(|data: &Schedule, _env: &Env| match data.validate() {
+        Err(ScheduleError::Empty) => format!("Warning: {}", ScheduleError::Empty),
+        _ => String::new(),
+    })
+    .with_text_color(ERROR_COLOR);
+
     Flex::column()
         .with_child(
             Flex::row()
                 .with_child(
                     Button::new("Add")
-                        .on_click(|_, data: &mut Arc<Vec<(usize, usize)>>, _| {
+                        .on_click(|_, data: &mut Arc<Vec<ScheduleBlock>>, _| {
                             let mut new_schedule: Vec<_> = (**data).clone();
-                            new_schedule
-                                .push(new_schedule.as_slice().last().unwrap_or(&(5, 30)).clone());
+                            new_schedule.push(
+                                new_schedule
+                                    .last()
+                                    .cloned()
+                                    .unwrap_or_else(|| ScheduleBlock::new(5, 30)),
+                            );
                             *data = Arc::new(new_schedule);
                         })
                         .padding(5.),
                 )
                 .with_child(
                     Button::new("Remove")
-                        .on_click(|_, data: &mut Arc<Vec<(usize, usize)>>, _| {
+                        .on_click(|_, data: &mut Arc<Vec<ScheduleBlock>>, _| {
                             let mut new_schedule: Vec<_> = (**data).clone();
                             new_schedule.pop();
                             *data = Arc::new(new_schedule);
                         })
                         .padding(5.),
-                ),
+                )
+                .lens(Schedule::blocks),
         )
-        .with_child(List::new(|| {
-            Flex::row()
-                .with_child(TextBox::new().lens(lens::Identity.map(
-                    |(x, _): &(usize, usize)| x.to_string(),
-                    |(x, _): &mut (usize, usize), y: String| *x = y.parse::<usize>().unwrap_or(*x),
-                )))
-                .with_child(TextBox::new().lens(lens::Identity.map(
-                    |(_, x): &(usize, usize)| x.to_string(),
-                    |(_, x): &mut (usize, usize), y: String| *x = y.parse::<usize>().unwrap_or(*x),
-                )))
-                .with_child(Label::new("s"))
-        }))
+        .with_child(
+            List::new(|| {
+                let block = Flex::row()
+                    .with_child(
+                        TextBox::new()
+                            .with_placeholder("Name")
+                            .lens(ScheduleBlock::name),
+                    )
+                    .with_child(TextBox::new().lens(ScheduleBlock::count.map(
+                        |x: &usize| x.to_string(),
+                        |x: &mut usize, y: String| *x = y.parse::<usize>().unwrap_or(*x),
+                    )))
+                    .with_child(Label::new("x"))
+                    .with_child(TextBox::new().lens(ScheduleBlock::duration.map(
+                        |x: &usize| x.to_string(),
+                        |x: &mut usize, y: String| *x = y.parse::<usize>().unwrap_or(*x),
+                    )))
+                    .with_child(Label::new("s"))
+                    .with_child(TextBox::new().with_placeholder("Break (s)").lens(
+                        ScheduleBlock::break_after.map(
+                            |x: &Option<usize>| x.map_or(String::new(), |x| x.to_string()),
+                            |x: &mut Option<usize>, y: String| {
+                                if y.trim().is_empty() {
+                                    *x = None;
+                                } else if let Ok(y) = y.trim().parse::<usize>() {
+                                    *x = Some(y);
+                                }
+                            },
+                        ),
+                    ))
+                    .with_child(TextBox::new().with_placeholder("Instruction").lens(
+                        ScheduleBlock::instruction.map(
+                            |x: &Option<String>| x.clone().unwrap_or_default(),
+                            |x: &mut Option<String>, y: String| {
+                                *x = if y.is_empty() { None } else { Some(y) };
+                            },
+                        ),
+                    ))
+                    .with_child(
+                        Label::new(|data: &ScheduleBlock, _env: &Env| {
+                            data.problem()
+                                .map_or(String::new(), |problem| format!("Block {}", problem))
+                        })
+                        .with_text_color(ERROR_COLOR),
+                    );
+
+                // Per-block options go on rows of their own so a block fits
+                // the window.
+                let images = Flex::row()
+                    .with_child(
+                        Checkbox::new("Pause before")
+                            .lens(ScheduleBlock::options.then(BlockOptions::pause_before)),
+                    )
+                    .with_child(Label::new("Keep image for"))
+                    .with_child(
+                        number_box().lens(ScheduleBlock::options.then(BlockOptions::hold_image)),
+                    )
+                    .with_child(Label::new("poses"))
+                    .with_child(
+                        Checkbox::new("Whole block")
+                            .lens(ScheduleBlock::options.then(BlockOptions::repeat_image)),
+                    )
+                    .with_child(
+                        Checkbox::new("B/W")
+                            .lens(ScheduleBlock::options.then(BlockOptions::black_and_white)),
+                    )
+                    .with_child(
+                        Checkbox::new("Mirror")
+                            .lens(ScheduleBlock::options.then(BlockOptions::mirrored)),
+                    )
+                    .with_child(TextBox::new().with_placeholder("All images").lens(
+                        ScheduleBlock::options.then(BlockOptions::pool).map(
+                            |x: &Option<String>| x.clone().unwrap_or_default(),
+                            |x: &mut Option<String>, y: String| {
+                                let y = y.trim();
+                                *x = if y.is_empty() {
+                                    None
+                                } else {
+                                    Some(y.to_owned())
+                                };
+                            },
+                        ),
+                    ));
+
+                let extras = Flex::row()
+                    .with_child(Label::new("Tick every"))
+                    .with_child(
+                        number_box().lens(ScheduleBlock::options.then(BlockOptions::metronome)),
+                    )
+                    .with_child(Label::new("s, accent last"))
+                    .with_child(
+                        number_box()
+                            .lens(ScheduleBlock::options.then(BlockOptions::accent_seconds)),
+                    )
+                    .with_child(Label::new("s"))
+                    .with_child(TextBox::new().with_placeholder("Prompts; ...").lens(
+                        ScheduleBlock::options.then(BlockOptions::prompts).map(
+                            |x: &Arc<Vec<String>>| x.join("; "),
+                            |x: &mut Arc<Vec<String>>, y: String| {
+                                let prompts: Vec<_> = y
+                                    .split(';')
+                                    .map(str::trim)
+                                    .filter(|prompt| !prompt.is_empty())
+                                    .map(str::to_owned)
+                                    .collect();
+                                if prompts != **x {
+                                    *x = Arc::new(prompts);
+                                }
+                            },
+                        ),
+                    ));
+
+                Flex::column()
+                    .cross_axis_alignment(CrossAxisAlignment::Start)
+                    .with_child(block)
+                    .with_child(images.padding((20., 0., 0., 0.)))
+                    .with_child(extras.padding((20., 0., 0., 5.)))
+            })
+            .lens(Schedule::blocks),
+        )
+        .with_child(total)
+        .with_child(schedule_error)
 }
 
 pub fn presentation_ui_builder() -> impl Widget<ProgramData> {
@@ -90,53 +588,43 @@ pub fn presentation_ui_builder() -> impl Widget<ProgramData> {
         AutoStepState::Playing(_) => "Pause".to_owned(),
         _ => "Play".to_owned(),
     })
-    .on_click(|ctx, data: &mut ProgramData, _| {
-        if data.images_paths.len() > 0 && data.config.schedule.len() > 0 {
-            ctx.submit_command(START_AUTO_STEP);
-        }
+    .on_click(|ctx, _data: &mut ProgramData, _| {
+        ctx.submit_command(START_AUTO_STEP);
     });
 
     let reload = Button::new("Reload").on_click(|_ctx, data: &mut ProgramData, _env| {
-        let mut end = false;
-        if let Some(auto_step_data) = data.state.get_data_mut() {
-            end = auto_step_data.set_next_image(data.images_paths.as_slice());
-            auto_step_data.time_left =
-                Some(auto_step_data.get_current_duration(&data.config.schedule) as f64);
-        }
+        data.reload();
+    });
 
-        if end {
-            data.prepare_images(false);
+    let pin = Button::new(|data: &ProgramData, _: &Env| {
+        if data.state.get_data().is_some_and(|data| data.pinned) {
+            "Unpin".to_owned()
+        } else {
+            "Pin".to_owned()
         }
+    })
+    .on_click(|_ctx, data: &mut ProgramData, _env| {
+        data.toggle_pin();
+    });
 
-        data.reset_transformations();
+    let back = Button::new("Back").on_click(|_ctx, data: &mut ProgramData, _env| {
+        data.back();
     });
 
     let skip = Button::new("Skip").on_click(|_ctx, data: &mut ProgramData, _env| {
-        let mut end = false;
-        if let Some(auto_step_data) = data.state.get_data_mut() {
-            end = auto_step_data.set_next_image(data.images_paths.as_slice());
-            auto_step_data.step_forward(&data.config.schedule);
-            auto_step_data.time_left =
-                Some(auto_step_data.get_current_duration(&data.config.schedule) as f64);
-        }
-
-        if end {
-            data.prepare_images(false);
-        }
-
-        data.reset_transformations();
+        data.advance(Step::Pose);
     });
 
     let skip_block = Button::new("Skip block").on_click(|_ctx, data: &mut ProgramData, _env| {
-        if let Some(auto_step_data) = data.state.get_data_mut() {
-            auto_step_data.set_next_image(data.images_paths.as_slice());
-            auto_step_data.step_forward_block(&data.config.schedule);
-            auto_step_data.time_left =
-                Some(auto_step_data.get_current_duration(&data.config.schedule) as f64);
-        }
-        data.reset_transformations();
+        data.advance(Step::Block);
     });
 
+    let adjust = |label: &str, seconds: f64| {
+        Button::new(label).on_click(move |_ctx, data: &mut ProgramData, _env| {
+            data.adjust_time(seconds);
+        })
+    };
+
     let stop = Button::new("Stop").on_click(|ctx, _data: &mut ProgramData, _env| {
         ctx.submit_command(STOP_AUTO_STEP);
     });
@@ -150,23 +638,140 @@ pub fn presentation_ui_builder() -> impl Widget<ProgramData> {
     });
 
     let current = Label::new(|data: &ProgramData, _env: &Env| {
-        format!(
-            "Current: {}",
-            data.state
-                .get_data()
-                .map_or("None".to_owned(), |data| format!("{:?}", data.current))
-        )
+        let data = match data.state.get_data() {
+            Some(data) => data,
+            None => return String::new(),
+        };
+        let (big_step, small_step) = data.current;
+        let block = data.schedule.block(big_step);
+        let name = if block.name.is_empty() {
+            format!("Block {}", big_step + 1)
+        } else {
+            block.name.clone()
+        };
+
+        if data.on_break {
+            format!("{}: break", name)
+        } else {
+            format!("{}: pose {} of {}", name, small_step + 1, block.count)
+        }
+    });
+
+    let session_time = Label::new(|data: &ProgramData, _env: &Env| {
+        data.state.get_data().map_or(String::new(), |data| {
+            let elapsed = data.elapsed();
+            let total = data.schedule.total_duration() as f64;
+            format!(
+                "Elapsed {}, remaining {}",
+                format_clock(elapsed),
+                format_clock(total - elapsed)
+            )
+        })
     });
 
     let time = Label::new(|data: &ProgramData, _env: &Env| {
         format!(
-            "Left: {:.2}",
-            data.state
-                .get_data()
-                .map_or(0., |data| data.time_left.unwrap_or(0.))
+            "Left: {}",
+            format_clock(
+                data.state
+                    .get_data()
+                    .map_or(0., |data| data.time_left.unwrap_or(0.))
+            )
         )
     })
-    .fix_width(50.0);
+    .env_scope(|env, data: &ProgramData| {
+        if data.timer_warning() {
+            env.set(theme::LABEL_COLOR, WARNING_COLOR);
+        }
+    })
+    .fix_width(80.0);
+
+    let progress = Either::new(
+        |data: &ProgramData, _env: &Env| {
+            data.config.timer.progress_bar && data.state.get_data().is_some()
+        },
+        ProgressBar::new()
+            .lens(ProgramData::state.map(
+                |x: &AutoStepState| x.get_data().map_or(0., AutoStepData::progress),
+                |_, _| (),
+            ))
+            .expand_width(),
+        Flex::row(),
+    );
+
+    let presets = Scroll::new(
+        List::new(|| {
+            Button::new(|data: &Preset, _env: &Env| data.name.clone()).on_click(
+                |ctx, data: &mut Preset, _| {
+                    ctx.submit_command(LOAD_PRESET.with(data.id));
+                },
+            )
+        })
+        .horizontal(),
+    )
+    .horizontal()
+    .lens(ProgramData::presets.then(Presets::list));
+
+    let today = Label::new(|data: &ProgramData, _env: &Env| {
+        let curriculum = match &data.curriculum {
+            Some(curriculum) => curriculum,
+            None => return String::new(),
+        };
+        match data.progress.today(curriculum) {
+            Some(day) => {
+                let day = &curriculum.days[day];
+                let mut text = format!("{}, {}", curriculum.name, day.name);
+                if !day.notes.is_empty() {
+                    text.push_str(&format!(": {}", day.notes));
+                }
+                text
+            }
+            None => format!("{} is complete", curriculum.name),
+        }
+    });
+
+    let start_today =
+        Button::new("Today's practice").on_click(|ctx, data: &mut ProgramData, _env| {
+            if data.load_todays_practice() {
+                ctx.submit_command(STOP_AUTO_STEP);
+                ctx.submit_command(START_AUTO_STEP);
+            }
+        });
+
+    let practice = Either::new(
+        |data: &ProgramData, _env: &Env| data.curriculum.is_some(),
+        Flex::row().with_child(start_today).with_child(today),
+        Flex::row(),
+    );
+
+    let error = Label::new(|data: &ProgramData, _env: &Env| match data.state {
+        AutoStepState::Stopped => data.error.clone().unwrap_or_default(),
+        _ => String::new(),
+    })
+    .with_text_color(ERROR_COLOR);
+
+    let summary = Label::new(
+        |data: &ProgramData, _env: &Env| match (&data.state, &data.summary) {
+            (AutoStepState::Stopped, Some(summary)) => {
+                let mut text = format!(
+                    "Session finished: {} poses, {} drawing, {} on breaks",
+                    summary.poses,
+                    format_duration(summary.drawing_time.round() as usize),
+                    format_duration(summary.break_time.round() as usize),
+                );
+                let adjustment = summary.time_adjustment.round();
+                if adjustment != 0. {
+                    text.push_str(&format!(
+                        ", {}{} adjusted",
+                        if adjustment > 0. { "+" } else { "-" },
+                        format_duration(adjustment.abs() as usize)
+                    ));
+                }
+                text
+            }
+            _ => String::new(),
+        },
+    );
 
     let image = Image::new(ImageBuf::empty())
         .fill_mode(FillStrat::Contain)
@@ -183,20 +788,68 @@ pub fn presentation_ui_builder() -> impl Widget<ProgramData> {
             },
         ));
 
+    let rest = Flex::column()
+        .with_child(
+            Label::new(|data: &ProgramData, _env: &Env| data.config.break_message.clone())
+                .with_text_size(32.),
+        )
+        .with_child(Label::new(|data: &ProgramData, _env: &Env| {
+            format!(
+                "Back in {}s",
+                data.state
+                    .get_data()
+                    .map_or(0., |data| data.time_left.unwrap_or(0.).ceil())
+            )
+        }))
+        .center();
+
+    let caption = Label::new(|data: &ProgramData, _env: &Env| {
+        data.state
+            .get_data()
+            .filter(|data| !data.on_break)
+            .map_or(String::new(), AutoStepData::caption)
+    })
+    .with_text_size(20.);
+
+    let stage = Either::new(
+        |data: &ProgramData, _env: &Env| data.state.get_data().is_some_and(|data| data.on_break),
+        rest,
+        LeadInCover::new(TimeOverlay::new(image)),
+    );
+
     Flex::column()
         .with_child(
             Flex::row()
                 .with_child(play)
                 .with_child(reload)
+                .with_child(pin)
+                .with_child(back)
                 .with_child(skip)
                 .with_child(skip_block)
+                .with_child(adjust("+30s", 30.))
+                .with_child(adjust("+1m", 60.))
+                .with_child(adjust("-30s", -30.))
                 .with_child(stop)
                 .with_child(black_and_white)
                 .with_child(mirrored)
                 .with_child(current)
                 .with_child(time),
         )
-        .with_flex_child(image, 1.0)
+        .with_child(
+            Flex::row()
+                .with_child(Label::new("Presets:"))
+                .with_child(presets),
+        )
+        .with_child(Timeline)
+        .with_child(UpcomingStrip::new())
+        .with_child(session_time)
+        .with_child(progress)
+        .with_child(practice)
+        .with_child(error)
+        .with_child(summary)
+        .with_child(caption)
+        .with_flex_child(stage, 1.0)
         .center()
         .controller(AutoStepControl::new())
+        .controller(KeyboardControl)
 }
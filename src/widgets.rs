@@ -0,0 +1,434 @@
+use druid::piet::{InterpolationMode, Text, TextLayout, TextLayoutBuilder};
+use druid::{
+    theme, BoxConstraints, Color, Data, Env, Event, EventCtx, ExtEventSink, FontFamily, LayoutCtx,
+    LifeCycle, LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Size, UpdateCtx, Widget,
+    WidgetId,
+};
+
+use crate::data::{ProgramData, JUMP_TO_BLOCK, REQUEUE_UPCOMING, THUMBNAIL_READY};
+use crate::images::ThumbnailCache;
+use crate::notation::format_clock;
+use crate::schedule::Schedule;
+
+use std::path::PathBuf;
+
+pub const WARNING_COLOR: Color = Color::rgb8(0xf0, 0xa0, 0x20);
+
+fn lead_in_left(data: &ProgramData) -> Option<f64> {
+    data.state.get_data().and_then(|data| data.lead_in)
+}
+
+/// Covers its child with a countdown while the session gets ready for a
+/// pose, dimming the child or hiding it completely.
+pub struct LeadInCover<W> {
+    child: W,
+}
+
+impl<W> LeadInCover<W> {
+    pub fn new(child: W) -> Self {
+        LeadInCover { child }
+    }
+}
+
+impl<W: Widget<ProgramData>> Widget<ProgramData> for LeadInCover<W> {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut ProgramData, env: &Env) {
+        self.child.event(ctx, event, data, env)
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &ProgramData,
+        env: &Env,
+    ) {
+        self.child.lifecycle(ctx, event, data, env)
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &ProgramData,
+        data: &ProgramData,
+        env: &Env,
+    ) {
+        if lead_in_left(old_data).map(f64::ceil) != lead_in_left(data).map(f64::ceil) {
+            ctx.request_paint();
+        }
+        self.child.update(ctx, old_data, data, env)
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &ProgramData,
+        env: &Env,
+    ) -> Size {
+        self.child.layout(ctx, bc, data, env)
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &ProgramData, env: &Env) {
+        self.child.paint(ctx, data, env);
+
+        let left = match lead_in_left(data) {
+            Some(left) => left,
+            None => return,
+        };
+
+        let cover = if data.config.lead_in.hide_image {
+            1.
+        } else {
+            0.7
+        };
+        let rect = ctx.size().to_rect();
+        ctx.fill(
+            rect,
+            &env.get(theme::WINDOW_BACKGROUND_COLOR).with_alpha(cover),
+        );
+
+        let layout = ctx
+            .text()
+            .new_text_layout(format!("{}", left.ceil()))
+            .font(FontFamily::SYSTEM_UI, 96.)
+            .text_color(env.get(theme::LABEL_COLOR))
+            .build()
+            .unwrap();
+        let size = layout.size();
+        let origin = Point::new(
+            (rect.width() - size.width) / 2.,
+            (rect.height() - size.height) / 2.,
+        );
+        ctx.draw_text(&layout, origin);
+    }
+}
+
+fn overlay_text(data: &ProgramData) -> Option<String> {
+    if !data.config.timer.overlay {
+        return None;
+    }
+    data.state
+        .get_data()
+        .filter(|data| !data.on_break && data.lead_in.is_none())
+        .and_then(|data| data.time_left)
+        .map(format_clock)
+}
+
+/// Shows the time left of the pose in large type over its child.
+pub struct TimeOverlay<W> {
+    child: W,
+}
+
+impl<W> TimeOverlay<W> {
+    pub fn new(child: W) -> Self {
+        TimeOverlay { child }
+    }
+}
+
+impl<W: Widget<ProgramData>> Widget<ProgramData> for TimeOverlay<W> {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut ProgramData, env: &Env) {
+        self.child.event(ctx, event, data, env)
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &ProgramData,
+        env: &Env,
+    ) {
+        self.child.lifecycle(ctx, event, data, env)
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &ProgramData,
+        data: &ProgramData,
+        env: &Env,
+    ) {
+        if overlay_text(old_data) != overlay_text(data)
+            || old_data.timer_warning() != data.timer_warning()
+        {
+            ctx.request_paint();
+        }
+        self.child.update(ctx, old_data, data, env)
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &ProgramData,
+        env: &Env,
+    ) -> Size {
+        self.child.layout(ctx, bc, data, env)
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &ProgramData, env: &Env) {
+        self.child.paint(ctx, data, env);
+
+        let text = match overlay_text(data) {
+            Some(text) => text,
+            None => return,
+        };
+        let color = if data.timer_warning() {
+            WARNING_COLOR
+        } else {
+            env.get(theme::LABEL_COLOR)
+        };
+
+        let layout = ctx
+            .text()
+            .new_text_layout(text)
+            .font(FontFamily::MONOSPACE, 72.)
+            .text_color(color)
+            .build()
+            .unwrap();
+        let size = ctx.size();
+        let text_size = layout.size();
+        let origin = Point::new(size.width - text_size.width - 20., 10.);
+        let background = Size::new(text_size.width + 20., text_size.height)
+            .to_rect()
+            .with_origin(origin - (10., 0.));
+        ctx.fill(
+            background,
+            &env.get(theme::WINDOW_BACKGROUND_COLOR).with_alpha(0.6),
+        );
+        ctx.draw_text(&layout, origin);
+    }
+}
+
+/// The schedule of the running session, or the configured one when stopped.
+fn timeline_schedule(data: &ProgramData) -> &Schedule {
+    data.state
+        .get_data()
+        .map_or(&data.config.schedule, |data| &data.schedule)
+}
+
+/// Position in the schedule the timeline highlights: block, pose and
+/// whether the session is on the break after the block.
+fn timeline_position(data: &ProgramData) -> Option<(usize, usize, bool)> {
+    data.state
+        .get_data()
+        .map(|data| (data.current.0, data.current.1, data.on_break))
+}
+
+/// Shows every block of the schedule as a run of pose segments, sized by
+/// their length and followed by the block's break. Clicking a block jumps
+/// the session there.
+pub struct Timeline;
+
+impl Timeline {
+    const HEIGHT: f64 = 24.;
+
+    /// Width of a second of the schedule at the given widget width.
+    fn scale(schedule: &Schedule, width: f64) -> f64 {
+        width / schedule.total_duration().max(1) as f64
+    }
+}
+
+impl Widget<ProgramData> for Timeline {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut ProgramData, _env: &Env) {
+        if let Event::MouseDown(mouse) = event {
+            if data.state.get_data().is_none() {
+                return;
+            }
+            let schedule = timeline_schedule(data);
+            let scale = Self::scale(schedule, ctx.size().width);
+
+            let mut end = 0.;
+            for (id, block) in schedule.blocks.iter().enumerate() {
+                end += block.total_duration() as f64 * scale;
+                if mouse.pos.x < end {
+                    ctx.submit_command(JUMP_TO_BLOCK.with(id));
+                    break;
+                }
+            }
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &ProgramData,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &ProgramData,
+        data: &ProgramData,
+        _env: &Env,
+    ) {
+        if !timeline_schedule(old_data).same(timeline_schedule(data))
+            || timeline_position(old_data) != timeline_position(data)
+        {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &ProgramData,
+        _env: &Env,
+    ) -> Size {
+        bc.constrain(Size::new(bc.max().width, Self::HEIGHT))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &ProgramData, env: &Env) {
+        let schedule = timeline_schedule(data);
+        let scale = Self::scale(schedule, ctx.size().width);
+        let position = timeline_position(data);
+
+        let done = env.get(theme::PRIMARY_DARK);
+        let current = env.get(theme::PRIMARY_LIGHT);
+        let upcoming = env.get(theme::BUTTON_LIGHT);
+        let rest = env.get(theme::BACKGROUND_LIGHT);
+        let border = env.get(theme::BORDER_DARK);
+
+        let mut x = 0.;
+        let mut segment = |ctx: &mut PaintCtx, seconds: usize, color: &Color| {
+            let rect = Rect::new(x, 0., x + seconds as f64 * scale, Self::HEIGHT);
+            ctx.fill(rect, color);
+            ctx.stroke(rect, &border, 1.);
+            x = rect.x1;
+        };
+
+        for (id, block) in schedule.blocks.iter().enumerate() {
+            for pose in 0..block.count {
+                let color = match position {
+                    Some((b, p, false)) if (id, pose) == (b, p) => &current,
+                    Some((b, p, on_break)) if (id, pose) < (b, p) || (id == b && on_break) => &done,
+                    _ => &upcoming,
+                };
+                segment(ctx, block.duration, color);
+            }
+            if let Some(length) = block.break_after {
+                let color = match position {
+                    Some((b, _, true)) if b == id => &current,
+                    Some((b, _, _)) if id < b => &done,
+                    _ => &rest,
+                };
+                segment(ctx, length, color);
+            }
+        }
+    }
+}
+
+/// Thumbnails of the images coming up next. Clicking one swaps it out for a
+/// later image.
+#[derive(Default)]
+pub struct UpcomingStrip {
+    cache: ThumbnailCache,
+    shown: Vec<PathBuf>,
+    /// Where each thumbnail was painted along with its position in `shown`,
+    /// for clicks.
+    rects: Vec<(Rect, usize)>,
+}
+
+impl UpcomingStrip {
+    const HEIGHT: f64 = 80.;
+    const GAP: f64 = 5.;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Has the thumbnails of the shown images decoded and sent back to this
+    /// widget.
+    fn request_thumbnails(&mut self, sink: ExtEventSink, target: WidgetId) {
+        self.cache.request(&self.shown, move |path, thumbnail| {
+            sink.submit_command(THUMBNAIL_READY, (path, thumbnail), target)
+                .ok();
+        });
+    }
+}
+
+impl Widget<ProgramData> for UpcomingStrip {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut ProgramData, _env: &Env) {
+        match event {
+            Event::MouseDown(mouse) => {
+                if let Some((_, id)) = self.rects.iter().find(|(rect, _)| rect.contains(mouse.pos))
+                {
+                    ctx.submit_command(REQUEUE_UPCOMING.with(*id));
+                }
+            }
+            Event::Command(cmd) if cmd.is(THUMBNAIL_READY) => {
+                let (path, thumbnail) = cmd.get_unchecked(THUMBNAIL_READY).clone();
+                self.cache.insert(path, thumbnail);
+                ctx.request_paint();
+            }
+            _ => (),
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &ProgramData,
+        _env: &Env,
+    ) {
+        if let LifeCycle::WidgetAdded = event {
+            self.shown = data.upcoming();
+            self.request_thumbnails(ctx.get_external_handle(), ctx.widget_id());
+        }
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        _old_data: &ProgramData,
+        data: &ProgramData,
+        _env: &Env,
+    ) {
+        let upcoming = data.upcoming();
+        if upcoming != self.shown {
+            self.shown = upcoming;
+            self.request_thumbnails(ctx.get_external_handle(), ctx.widget_id());
+            ctx.request_layout();
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &ProgramData,
+        _env: &Env,
+    ) -> Size {
+        let height = if self.shown.is_empty() {
+            0.
+        } else {
+            Self::HEIGHT
+        };
+        bc.constrain(Size::new(bc.max().width, height))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, _data: &ProgramData, _env: &Env) {
+        self.rects.clear();
+        let mut x = 0.;
+
+        for (id, path) in self.shown.iter().enumerate() {
+            let thumbnail = match self.cache.get(path) {
+                Some(thumbnail) => thumbnail,
+                None => continue,
+            };
+            let size = thumbnail.size();
+            let width = size.width * Self::HEIGHT / size.height.max(1.);
+            let rect = Rect::new(x, 0., x + width, Self::HEIGHT);
+
+            let image = thumbnail.to_image(ctx.render_ctx);
+            ctx.draw_image(&image, rect, InterpolationMode::Bilinear);
+            self.rects.push((rect, id));
+            x = rect.x1 + Self::GAP;
+        }
+    }
+}